@@ -0,0 +1,237 @@
+// Cycle detection over any deterministic step function `State -> State`.
+//
+// Starting from some state x0 and repeatedly applying the step function gives
+// x0, x1, x2, ...  Over a finite state space this sequence must eventually
+// repeat: after a tail of `tail` states it loops with period `period`, so that
+// x(n) == x(n + period) for every n >= tail.
+//
+// Floyd's and Brent's algorithms find the cycle in constant memory but only
+// need `PartialEq`; the hash-based detector records every state it visits so it
+// can also answer questions about the states themselves (which positions hit a
+// target, what the state is after a huge number of steps).
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Cycle {
+    pub tail: usize,
+    pub period: usize,
+}
+
+impl Cycle {
+    // Map any step number onto the equivalent step within the tail or the first
+    // pass of the cycle, i.e. a value in 0..(tail + period)
+    #[allow(dead_code)]
+    pub fn reduce(&self, step: u128) -> usize {
+        let tail = self.tail as u128;
+        if step < tail {
+            step as usize
+        } else {
+            (tail + (step - tail) % self.period as u128) as usize
+        }
+    }
+}
+
+#[allow(dead_code)]
+pub fn floyd<S, F>(start: S, step: F) -> Cycle
+where
+    S: Clone + PartialEq,
+    F: Fn(S) -> S,
+{
+    // Tortoise moves one step at a time, hare two, until they meet inside the cycle
+    let mut tortoise = step(start.clone());
+    let mut hare = step(step(start.clone()));
+    while tortoise != hare {
+        tortoise = step(tortoise);
+        hare = step(step(hare));
+    }
+
+    // Restart the tortoise; moving both one step at a time they meet at the cycle start
+    let mut tail = 0;
+    tortoise = start;
+    while tortoise != hare {
+        tortoise = step(tortoise);
+        hare = step(hare);
+        tail += 1;
+    }
+
+    let mut period = 1;
+    hare = step(tortoise.clone());
+    while tortoise != hare {
+        hare = step(hare);
+        period += 1;
+    }
+
+    Cycle { tail, period }
+}
+
+#[allow(dead_code)]
+pub fn brent<S, F>(start: S, step: F) -> Cycle
+where
+    S: Clone + PartialEq,
+    F: Fn(S) -> S,
+{
+    // Find the period by searching successive powers of two
+    let mut power = 1;
+    let mut period = 1;
+    let mut tortoise = start.clone();
+    let mut hare = step(start.clone());
+    while tortoise != hare {
+        if power == period {
+            tortoise = hare.clone();
+            power *= 2;
+            period = 0;
+        }
+        hare = step(hare);
+        period += 1;
+    }
+
+    // Hare starts `period` steps ahead; move both until they meet at the cycle start
+    let mut tortoise = start.clone();
+    let mut hare = start;
+    for _ in 0..period {
+        hare = step(hare);
+    }
+    let mut tail = 0;
+    while tortoise != hare {
+        tortoise = step(tortoise);
+        hare = step(hare);
+        tail += 1;
+    }
+
+    Cycle { tail, period }
+}
+
+// Step `start` forward `n` times, using a previously detected cycle to skip
+// whole loops.  Only ever takes fewer than tail + period real steps.
+#[allow(dead_code)]
+pub fn state_at<S, F>(start: S, step: F, cycle: &Cycle, n: u128) -> S
+where
+    F: Fn(S) -> S,
+{
+    let mut current = start;
+    for _ in 0..cycle.reduce(n) {
+        current = step(current);
+    }
+    current
+}
+
+// Every state visited up to (but not including) the first repeat, along with
+// the cycle they form.
+pub struct History<S> {
+    pub states: Vec<S>,
+    pub cycle: Cycle,
+}
+
+impl<S> History<S>
+where
+    S: Clone + Eq + Hash,
+{
+    pub fn detect<F>(start: S, step: F) -> Self
+    where
+        F: Fn(S) -> S,
+    {
+        let mut seen: HashMap<S, usize> = HashMap::new();
+        let mut states = Vec::new();
+        let mut current = start;
+
+        loop {
+            if let Some(&first_seen) = seen.get(&current) {
+                let cycle = Cycle {
+                    tail: first_seen,
+                    period: states.len() - first_seen,
+                };
+                return Self { states, cycle };
+            }
+            seen.insert(current.clone(), states.len());
+            states.push(current.clone());
+            current = step(current);
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn state_at(&self, n: u128) -> &S {
+        &self.states[self.cycle.reduce(n)]
+    }
+
+    // Step numbers within the tail at which the state matches
+    pub fn tail_positions<P>(&self, is_target: P) -> Vec<usize>
+    where
+        P: Fn(&S) -> bool,
+    {
+        (0..self.cycle.tail)
+            .filter(|&i| is_target(&self.states[i]))
+            .collect()
+    }
+
+    // Step numbers within the first pass of the cycle at which the state matches.
+    // Each one also matches at every later multiple of the period.
+    pub fn cycle_positions<P>(&self, is_target: P) -> Vec<usize>
+    where
+        P: Fn(&S) -> bool,
+    {
+        (self.cycle.tail..self.states.len())
+            .filter(|&i| is_target(&self.states[i]))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 0 -> 1 -> 2 -> 3 -> 4 -> 5 -> 6 -> 3 ...
+    fn rho(x: u32) -> u32 {
+        if x == 6 {
+            3
+        } else {
+            x + 1
+        }
+    }
+
+    #[test]
+    fn check_floyd_and_brent_agree() {
+        let expected = Cycle { tail: 3, period: 4 };
+        assert_eq!(floyd(0, rho), expected);
+        assert_eq!(brent(0, rho), expected);
+        assert_eq!(History::detect(0, rho).cycle, expected);
+    }
+
+    #[test]
+    fn check_pure_cycle() {
+        let step = |x: u64| (x + 7) % 10;
+        let expected = Cycle {
+            tail: 0,
+            period: 10,
+        };
+        assert_eq!(floyd(0, step), expected);
+        assert_eq!(brent(0, step), expected);
+    }
+
+    #[test]
+    fn check_fixed_point() {
+        let step = |x: u64| x / 2;
+        assert_eq!(brent(100, step), Cycle { tail: 7, period: 1 });
+        assert_eq!(floyd(100, step), Cycle { tail: 7, period: 1 });
+    }
+
+    #[test]
+    fn check_fast_forward() {
+        let history = History::detect(0, rho);
+        assert_eq!(*history.state_at(2), 2);
+        assert_eq!(*history.state_at(7), 3);
+        assert_eq!(*history.state_at(1_000_000_000_000_000_000_000), 4);
+        assert_eq!(
+            state_at(0, rho, &history.cycle, 1_000_000_000_000_000_000_003),
+            3
+        );
+    }
+
+    #[test]
+    fn check_target_positions() {
+        let history = History::detect(0, rho);
+        assert_eq!(history.tail_positions(|x| x % 2 == 0), vec![0, 2]);
+        assert_eq!(history.cycle_positions(|x| x % 2 == 0), vec![4, 6]);
+    }
+}
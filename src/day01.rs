@@ -1,4 +1,5 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::ops::Range;
 
 pub fn day01(input_lines: &str) -> (String, String) {
     let input = input_lines;
@@ -9,75 +10,334 @@ pub fn day01(input_lines: &str) -> (String, String) {
 }
 
 fn part1(input_lines: &str) -> u32 {
-    input_lines
-        .lines()
-        .map(|l| {
-            let calibration_val =
-                first_digit_of(&mut l.chars()) + &first_digit_of(&mut l.chars().rev());
-            calibration_val.parse::<u32>().unwrap()
-        })
-        .sum()
+    let matcher = DigitMatcher::new(&Vocabulary::new().with_digits());
+    CalibrationReport::create(input_lines, &matcher).total()
 }
 
 fn part2(input_lines: &str) -> u32 {
-    input_lines
-        .lines()
-        .map(prune_trebuchet_line)
-        .map(|chars| {
-            let calibration_val = chars[0].to_string() + &chars[chars.len() - 1].to_string();
-            calibration_val.parse::<u32>().unwrap()
-        })
-        .sum()
-}
-
-// Return only the numbers from the input
-// Numbers are either ascii digits, or written out numbers one through nine
-// All numbers in the output are written as ascii digits
-fn prune_trebuchet_line(input: &str) -> Vec<char> {
-    let mut numbers = HashMap::new();
-
-    numbers.insert("one", '1');
-    numbers.insert("two", '2');
-    numbers.insert("three", '3');
-    numbers.insert("four", '4');
-    numbers.insert("five", '5');
-    numbers.insert("six", '6');
-    numbers.insert("seven", '7');
-    numbers.insert("eight", '8');
-    numbers.insert("nine", '9');
-
-    let mut output = Vec::new();
-
-    // Scan through with window-size 5 (max length of a number)
-    // Add sufficient padding at the end for this window
-    let input_string = input.to_string() + "xxxx";
-
-    for chars_window in input_string.chars().collect::<Vec<_>>().windows(5) {
-        if chars_window[0].is_ascii_digit() {
-            output.push(chars_window[0])
-        } else {
-            // Check if this window starts with a written out number
-            let window = chars_window.iter().collect::<String>();
-            for english_number in numbers.keys() {
-                if window.starts_with(english_number) {
-                    output.push(numbers.get(*english_number).unwrap().to_owned())
+    let matcher = DigitMatcher::new(&Vocabulary::english());
+    CalibrationReport::create(input_lines, &matcher).total()
+}
+
+// Which digits were picked out of each line, and where from.  Lines without any
+// digits are kept (with no digits chosen) so they can be flagged up.
+struct CalibrationReport {
+    lines: Vec<LineReport>,
+}
+
+struct LineReport {
+    line_number: usize,
+    text: String,
+    first: Option<ChosenDigit>,
+    last: Option<ChosenDigit>,
+}
+
+struct ChosenDigit {
+    value: u32,
+    span: Range<usize>,
+    spelled: bool,
+}
+
+impl CalibrationReport {
+    fn create(input_lines: &str, matcher: &DigitMatcher) -> Self {
+        let lines = input_lines
+            .lines()
+            .enumerate()
+            .map(|(i, line)| {
+                let matches = matcher.find_all(line);
+                let choose = |m: &TokenMatch| {
+                    let span = m.offset..m.offset + m.len;
+                    ChosenDigit {
+                        value: m.value,
+                        spelled: !line[span.clone()].chars().all(|c| c.is_ascii_digit()),
+                        span,
+                    }
+                };
+                LineReport {
+                    line_number: i + 1,
+                    text: line.to_string(),
+                    first: matches.first().map(choose),
+                    last: matches.last().map(choose),
                 }
-            }
-            // Otherwise, window starts with an uninteresting char which we can discard
+            })
+            .collect();
+        Self { lines }
+    }
+
+    // Sum of calibration values, skipping any lines without digits
+    fn total(&self) -> u32 {
+        self.lines.iter().filter_map(LineReport::value).sum()
+    }
+}
+
+// Views of the report for tracking down a wrong answer
+#[allow(dead_code)]
+impl CalibrationReport {
+    fn lines_without_digits(&self) -> Vec<usize> {
+        self.lines
+            .iter()
+            .filter(|line| line.value().is_none())
+            .map(|line| line.line_number)
+            .collect()
+    }
+
+    fn to_table(&self) -> String {
+        let mut table = format!(
+            "{:>5}  {:<16}  {:<16}  {:>5}  {}\n",
+            "line", "first", "last", "value", "text"
+        );
+        for line in &self.lines {
+            let describe = |digit: &Option<ChosenDigit>| match digit {
+                Some(digit) => format!(
+                    "{} {:?} @{}..{}",
+                    digit.value,
+                    &line.text[digit.span.clone()],
+                    digit.span.start,
+                    digit.span.end
+                ),
+                None => String::from("-"),
+            };
+            let value = line
+                .value()
+                .map_or(String::from("NONE"), |value| value.to_string());
+            table += &format!(
+                "{:>5}  {:<16}  {:<16}  {:>5}  {}\n",
+                line.line_number,
+                describe(&line.first),
+                describe(&line.last),
+                value,
+                line.text
+            );
+        }
+        table += &format!("total: {}\n", self.total());
+        let missing = self.lines_without_digits();
+        if !missing.is_empty() {
+            table += &format!("lines without digits: {:?}\n", missing);
+        }
+        table
+    }
+
+    fn to_json(&self) -> String {
+        let lines = self
+            .lines
+            .iter()
+            .map(|line| {
+                let describe = |digit: &Option<ChosenDigit>| match digit {
+                    Some(digit) => format!(
+                        "{{\"value\": {}, \"start\": {}, \"end\": {}, \"source\": {}, \"kind\": \"{}\"}}",
+                        digit.value,
+                        digit.span.start,
+                        digit.span.end,
+                        json_string(&line.text[digit.span.clone()]),
+                        if digit.spelled { "word" } else { "digit" }
+                    ),
+                    None => String::from("null"),
+                };
+                format!(
+                    "{{\"line\": {}, \"text\": {}, \"first\": {}, \"last\": {}, \"value\": {}, \"missing_digits\": {}}}",
+                    line.line_number,
+                    json_string(&line.text),
+                    describe(&line.first),
+                    describe(&line.last),
+                    line.value().map_or(String::from("null"), |v| v.to_string()),
+                    line.value().is_none()
+                )
+            })
+            .collect::<Vec<_>>();
+        format!(
+            "{{\"total\": {}, \"lines\": [{}]}}",
+            self.total(),
+            lines.join(", ")
+        )
+    }
+}
+
+impl LineReport {
+    // The first value's digits followed by the last value's, so "ten" and "twenty"
+    // make 1020 rather than 120
+    fn value(&self) -> Option<u32> {
+        let (first, last) = (self.first.as_ref()?.value, self.last.as_ref()?.value);
+        let shift = 10_u32.pow(last.checked_ilog10().unwrap_or(0) + 1);
+        Some(first * shift + last)
+    }
+}
+
+fn json_string(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted += "\\\"",
+            '\\' => quoted += "\\\\",
+            c if c.is_control() => quoted += &format!("\\u{:04x}", c as u32),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+const ENGLISH_ONES: [&str; 9] = [
+    "one", "two", "three", "four", "five", "six", "seven", "eight", "nine",
+];
+#[allow(dead_code)]
+const ENGLISH_TEENS: [&str; 11] = [
+    "ten",
+    "eleven",
+    "twelve",
+    "thirteen",
+    "fourteen",
+    "fifteen",
+    "sixteen",
+    "seventeen",
+    "eighteen",
+    "nineteen",
+    "twenty",
+];
+#[allow(dead_code)]
+const FRENCH_ONES: [&str; 9] = [
+    "un", "deux", "trois", "quatre", "cinq", "six", "sept", "huit", "neuf",
+];
+#[allow(dead_code)]
+const GERMAN_ONES: [&str; 9] = [
+    "eins", "zwei", "drei", "vier", "fünf", "sechs", "sieben", "acht", "neun",
+];
+
+// The tokens to search for, and the number each one stands for
+#[derive(Default)]
+struct Vocabulary(Vec<(String, u32)>);
+
+impl Vocabulary {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    // Ascii digits plus the English words one through nine
+    fn english() -> Self {
+        Self::new().with_digits().with_words(&ENGLISH_ONES, 1)
+    }
+
+    fn with_digits(self) -> Self {
+        (0..=9).fold(self, |vocab, n| vocab.with_token(&n.to_string(), n))
+    }
+
+    // Consecutive words standing for first_value, first_value + 1, ...
+    fn with_words(self, words: &[&str], first_value: u32) -> Self {
+        words
+            .iter()
+            .zip(first_value..)
+            .fold(self, |vocab, (word, value)| vocab.with_token(word, value))
+    }
+
+    // Adding a token that's already there with the same value does nothing, so shared
+    // words like "six" in English and French are only matched once
+    fn with_token(mut self, token: &str, value: u32) -> Self {
+        assert!(!token.is_empty(), "Can't search for an empty token");
+        if !self.0.iter().any(|(t, v)| t == token && *v == value) {
+            self.0.push((token.to_string(), value));
         }
+        self
     }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct TokenMatch {
+    // Byte offsets into the searched text
+    offset: usize,
+    len: usize,
+    value: u32,
+}
 
-    output
+// Aho-Corasick automaton over the bytes of every token in a vocabulary.  Finds all
+// matches, including overlapping ones like "eightwo", in a single pass over the text.
+struct DigitMatcher {
+    nodes: Vec<TrieNode>,
+    // (length, value) of each token, indexed by TrieNode::outputs
+    tokens: Vec<(usize, u32)>,
 }
 
-fn first_digit_of<I>(input: &mut I) -> String
-where
-    I: Iterator<Item = char>,
-{
-    input
-        .find(|c| c.is_ascii_digit())
-        .expect("Input did not contain a digit")
-        .to_string()
+#[derive(Default)]
+struct TrieNode {
+    children: HashMap<u8, usize>,
+    // Node for the longest proper suffix of this node's string that is also in the trie
+    fail: usize,
+    // Tokens ending at this node, including those reached by following fail links
+    outputs: Vec<usize>,
+}
+
+impl DigitMatcher {
+    fn new(vocabulary: &Vocabulary) -> Self {
+        let mut nodes = vec![TrieNode::default()];
+        let mut tokens = Vec::new();
+
+        for (token, value) in &vocabulary.0 {
+            let mut current = 0;
+            for byte in token.bytes() {
+                current = match nodes[current].children.get(&byte) {
+                    Some(&child) => child,
+                    None => {
+                        nodes.push(TrieNode::default());
+                        let child = nodes.len() - 1;
+                        nodes[current].children.insert(byte, child);
+                        child
+                    }
+                };
+            }
+            nodes[current].outputs.push(tokens.len());
+            tokens.push((token.len(), *value));
+        }
+
+        // Breadth first, so every fail link points at a node that's already done
+        let mut queue: VecDeque<usize> = nodes[0].children.values().copied().collect();
+        while let Some(parent) = queue.pop_front() {
+            let children: Vec<(u8, usize)> = nodes[parent]
+                .children
+                .iter()
+                .map(|(byte, child)| (*byte, *child))
+                .collect();
+            for (byte, child) in children {
+                let mut fallback = nodes[parent].fail;
+                let fail = loop {
+                    if let Some(&next) = nodes[fallback].children.get(&byte) {
+                        break next;
+                    }
+                    if fallback == 0 {
+                        break 0;
+                    }
+                    fallback = nodes[fallback].fail;
+                };
+                nodes[child].fail = fail;
+                let inherited = nodes[fail].outputs.clone();
+                nodes[child].outputs.extend(inherited);
+                queue.push_back(child);
+            }
+        }
+
+        Self { nodes, tokens }
+    }
+
+    // Every match in the text, ordered by where it starts
+    fn find_all(&self, text: &str) -> Vec<TokenMatch> {
+        let mut matches = Vec::new();
+        let mut current = 0;
+
+        for (i, byte) in text.bytes().enumerate() {
+            while current != 0 && !self.nodes[current].children.contains_key(&byte) {
+                current = self.nodes[current].fail;
+            }
+            current = *self.nodes[current].children.get(&byte).unwrap_or(&0);
+            for &token in &self.nodes[current].outputs {
+                let (len, value) = self.tokens[token];
+                matches.push(TokenMatch {
+                    offset: i + 1 - len,
+                    len,
+                    value,
+                });
+            }
+        }
+
+        matches.sort_by_key(|m| (m.offset, m.len));
+        matches
+    }
 }
 
 #[cfg(test)]
@@ -95,4 +355,96 @@ mod tests {
         let input = "two1nine\neightwothree\nabcone2threexyz\nxtwone3four\n4nineeightseven2\nzoneight234\n7pqrstsixteen\n";
         assert_eq!(part2(input), 281)
     }
+
+    #[test]
+    fn check_report_table() {
+        let matcher = DigitMatcher::new(&Vocabulary::english());
+        let report = CalibrationReport::create("two1nine\nabc\nzoneight234", &matcher);
+        assert_eq!(report.total(), 29 + 14);
+        assert_eq!(report.lines_without_digits(), vec![2]);
+        let expected = [
+            " line  first             last              value  text",
+            "    1  2 \"two\" @0..3     9 \"nine\" @4..8       29  two1nine",
+            "    2  -                 -                  NONE  abc",
+            "    3  1 \"one\" @1..4     4 \"4\" @10..11        14  zoneight234",
+            "total: 43",
+            "lines without digits: [2]",
+            "",
+        ];
+        assert_eq!(report.to_table(), expected.join("\n"));
+    }
+
+    #[test]
+    fn check_report_json() {
+        let matcher = DigitMatcher::new(&Vocabulary::english());
+        let report = CalibrationReport::create("a\"7\nxyz", &matcher);
+        let digit = r#"{"value": 7, "start": 2, "end": 3, "source": "7", "kind": "digit"}"#;
+        let expected = format!(
+            r#"{{"total": 77, "lines": [{{"line": 1, "text": "a\"7", "first": {}, "last": {}, "value": 77, "missing_digits": false}}, {{"line": 2, "text": "xyz", "first": null, "last": null, "value": null, "missing_digits": true}}]}}"#,
+            digit, digit
+        );
+        assert_eq!(report.to_json(), expected);
+    }
+
+    #[test]
+    fn check_overlapping_matches() {
+        let matcher = DigitMatcher::new(&Vocabulary::english());
+        let matches = matcher.find_all("xeightwo3ne");
+        assert_eq!(
+            matches,
+            vec![
+                TokenMatch {
+                    offset: 1,
+                    len: 5,
+                    value: 8
+                },
+                TokenMatch {
+                    offset: 5,
+                    len: 3,
+                    value: 2
+                },
+                TokenMatch {
+                    offset: 8,
+                    len: 1,
+                    value: 3
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn check_configurable_vocabularies() {
+        let vocabulary = Vocabulary::new()
+            .with_token("zero", 0)
+            .with_words(&ENGLISH_ONES, 1)
+            .with_words(&ENGLISH_TEENS, 10)
+            .with_words(&GERMAN_ONES, 1)
+            .with_words(&FRENCH_ONES, 1)
+            .with_token("dozen", 12);
+        let matcher = DigitMatcher::new(&vocabulary);
+        let values = |text| {
+            matcher
+                .find_all(text)
+                .iter()
+                .map(|m| m.value)
+                .collect::<Vec<_>>()
+        };
+
+        // "seventeen" also contains "seven"
+        assert_eq!(values("seventeen"), vec![7, 17]);
+        assert_eq!(values("twentyone"), vec![20, 1]);
+        assert_eq!(values("zerodozenfünf"), vec![0, 12, 5]);
+        assert_eq!(values("sixsechs"), vec![6, 6]);
+        assert_eq!(values("quatreinsept"), vec![4, 1, 7]);
+    }
+
+    #[test]
+    fn check_multi_digit_values() {
+        let vocabulary = Vocabulary::english().with_words(&ENGLISH_TEENS, 10);
+        let matcher = DigitMatcher::new(&vocabulary);
+        let report = CalibrationReport::create("tenabctwenty\nthree0\nsixteen", &matcher);
+        let values: Vec<Option<u32>> = report.lines.iter().map(LineReport::value).collect();
+        // "sixteen" starts with "six" and ends with "sixteen"
+        assert_eq!(values, vec![Some(1020), Some(30), Some(616)]);
+    }
 }
@@ -1,54 +1,115 @@
 use std::collections::HashMap;
 
 pub fn day02(input_lines: &str) -> (String, String) {
-    let answer1 = part1(input_lines);
-    let answer2 = part2(input_lines);
+    let games = Games::create(input_lines);
+    let answer1 = part1(&games);
+    let answer2 = part2(&games);
     (format!("{}", answer1), format!("{}", answer2))
 }
 
-fn part1(input_lines: &str) -> u32 {
-    let true_bag = CubeSet::create_from_rgb(12, 13, 14);
-    input_lines
-        .lines()
-        .map(Game::new)
-        .filter(|game| game.is_compatible_with(&true_bag))
-        .map(|game| game.id)
-        .sum()
+fn part1(games: &Games) -> u32 {
+    let true_bag = games.bag(&[("red", 12), ("green", 13), ("blue", 14)]);
+    games.possible_with(&true_bag).iter().sum()
 }
 
-fn part2(input_lines: &str) -> u32 {
-    input_lines
-        .lines()
-        .map(Game::new)
-        .map(|game| {
-            game.min_of_colour("red") * game.min_of_colour("green") * game.min_of_colour("blue")
-        })
+fn part2(games: &Games) -> u64 {
+    games
+        .games
+        .iter()
+        .map(|game| games.power(&game.min_bag(), &["red", "green", "blue"]))
         .sum()
 }
+
+type ColourId = usize;
+
+// Every colour seen in the input, each given a small id to index CubeSets by
+#[derive(Default)]
+struct ColourRegistry {
+    names: Vec<String>,
+    ids: HashMap<String, ColourId>,
+}
+
+impl ColourRegistry {
+    fn intern(&mut self, name: &str) -> ColourId {
+        if let Some(&id) = self.ids.get(name) {
+            return id;
+        }
+        let id = self.names.len();
+        self.names.push(name.to_string());
+        self.ids.insert(name.to_string(), id);
+        id
+    }
+
+    fn id(&self, name: &str) -> Option<ColourId> {
+        self.ids.get(name).copied()
+    }
+
+    fn name(&self, id: ColourId) -> &str {
+        &self.names[id]
+    }
+
+    fn len(&self) -> usize {
+        self.names.len()
+    }
+}
+
+#[derive(Clone, Debug, Default)]
 struct CubeSet {
-    cubes_map: HashMap<String, u32>,
+    // Indexed by ColourId; colours past the end have no cubes
+    counts: Vec<u32>,
 }
 
+impl PartialEq for CubeSet {
+    fn eq(&self, other: &Self) -> bool {
+        let len = usize::max(self.counts.len(), other.counts.len());
+        (0..len).all(|colour| self.count(colour) == other.count(colour))
+    }
+}
+
+impl Eq for CubeSet {}
+
 impl CubeSet {
+    fn count(&self, colour: ColourId) -> u32 {
+        *self.counts.get(colour).unwrap_or(&0)
+    }
+
+    fn set(&mut self, colour: ColourId, count: u32) {
+        if self.counts.len() <= colour {
+            self.counts.resize(colour + 1, 0);
+        }
+        self.counts[colour] = count;
+    }
+
     fn is_compatible_with(&self, true_bag: &CubeSet) -> bool {
-        true_bag.total_cubes() >= self.total_cubes()
-            && true_bag
-                .cubes_map
-                .iter()
-                .all(|(colour, val)| val >= self.cubes_map.get(colour).unwrap_or(&0))
+        self.counts
+            .iter()
+            .enumerate()
+            .all(|(colour, count)| true_bag.count(colour) >= *count)
     }
 
-    fn total_cubes(&self) -> u32 {
-        self.cubes_map.values().sum()
+    // The smallest set containing both sets
+    fn max(&self, other: &CubeSet) -> CubeSet {
+        let len = usize::max(self.counts.len(), other.counts.len());
+        CubeSet {
+            counts: (0..len)
+                .map(|colour| u32::max(self.count(colour), other.count(colour)))
+                .collect(),
+        }
     }
 
-    fn create_from_rgb(red: u32, green: u32, blue: u32) -> Self {
-        let mut obs = CubeSet {
-            cubes_map: HashMap::new(),
-        };
-        obs.cubes_map.insert(String::from("red"), red);
-        obs.cubes_map.insert(String::from("blue"), blue);
-        obs.cubes_map.insert(String::from("green"), green);
+    fn power(&self, colours: &[ColourId]) -> u64 {
+        colours
+            .iter()
+            .map(|colour| u64::from(self.count(*colour)))
+            .product()
+    }
+
+    #[allow(dead_code)]
+    fn create_from_rgb(colours: &mut ColourRegistry, red: u32, green: u32, blue: u32) -> Self {
+        let mut obs = CubeSet::default();
+        obs.set(colours.intern("red"), red);
+        obs.set(colours.intern("green"), green);
+        obs.set(colours.intern("blue"), blue);
         obs
     }
 }
@@ -59,7 +120,7 @@ struct Game {
 }
 
 impl Game {
-    fn new(input_line: &str) -> Self {
+    fn new(input_line: &str, colours: &mut ColourRegistry) -> Self {
         let mut initial_split = input_line.split(':');
         let id: u32 = initial_split.next().unwrap()[5..].parse().unwrap();
         let mut game = Game {
@@ -68,15 +129,15 @@ impl Game {
         };
         for obs in initial_split.next().unwrap().split(';') {
             // obs =  1 green, 2 red, 6 blue; 4 red, 1 green, 3 blue; 7 blue, 5 green; 6 blue, 2 red, 1 green
-            let mut cubes_map = HashMap::new();
+            let mut cubes = CubeSet::default();
             for cube in obs.split(',').map(|s| s.trim()) {
                 // cube = 1 green
                 let mut split = cube.split(' ');
                 let value = split.next().unwrap().parse().unwrap();
-                let colour = split.next().unwrap();
-                cubes_map.insert(colour.to_string(), value);
+                let colour = colours.intern(split.next().unwrap());
+                cubes.set(colour, value);
             }
-            game.observations.push(CubeSet { cubes_map })
+            game.observations.push(cubes)
         }
         game
     }
@@ -87,46 +148,361 @@ impl Game {
             .all(|obs| obs.is_compatible_with(true_bag))
     }
 
-    fn min_of_colour(&self, colour: &str) -> u32 {
+    fn min_bag(&self) -> CubeSet {
         self.observations
             .iter()
-            .map(|obs| *obs.cubes_map.get(colour).unwrap_or(&0))
-            .max()
-            .unwrap()
+            .fold(CubeSet::default(), |bag, obs| bag.max(obs))
+    }
+
+    #[allow(dead_code)]
+    fn min_of_colour(&self, colour: ColourId) -> u32 {
+        self.min_bag().count(colour)
+    }
+}
+
+struct Games {
+    colours: ColourRegistry,
+    games: Vec<Game>,
+}
+
+impl Games {
+    fn create(input_lines: &str) -> Self {
+        let mut colours = ColourRegistry::default();
+        let games = input_lines
+            .lines()
+            .map(|line| Game::new(line, &mut colours))
+            .collect();
+        Self { colours, games }
+    }
+
+    // Build a bag by colour name.  Colours that never appear in any game can't
+    // affect any query, so are left out.
+    fn bag(&self, cubes: &[(&str, u32)]) -> CubeSet {
+        let mut bag = CubeSet::default();
+        for (name, count) in cubes {
+            if let Some(colour) = self.colours.id(name) {
+                bag.set(colour, *count);
+            }
+        }
+        bag
+    }
+
+    // Ids of the games that could have been played with this bag
+    fn possible_with(&self, bag: &CubeSet) -> Vec<u32> {
+        self.games
+            .iter()
+            .filter(|game| game.is_compatible_with(bag))
+            .map(|game| game.id)
+            .collect()
+    }
+
+    // Product of the cube counts of the named colours.  A colour that's never seen
+    // has no cubes, so the power is zero.
+    fn power(&self, bag: &CubeSet, colour_names: &[&str]) -> u64 {
+        let colours: Option<Vec<ColourId>> = colour_names
+            .iter()
+            .map(|name| self.colours.id(name))
+            .collect();
+        colours.map_or(0, |colours| bag.power(&colours))
     }
 }
 
+// Queries beyond what the puzzle asks for
+#[allow(dead_code)]
+impl Games {
+    // The smallest bag that every game could have been played with
+    fn minimal_bag(&self) -> CubeSet {
+        self.games
+            .iter()
+            .fold(CubeSet::default(), |bag, game| bag.max(&game.min_bag()))
+    }
+
+    // The smallest bag that all the given games could have been played with
+    fn minimal_bag_for(&self, ids: &[u32]) -> CubeSet {
+        self.games
+            .iter()
+            .filter(|game| ids.contains(&game.id))
+            .fold(CubeSet::default(), |bag, game| bag.max(&game.min_bag()))
+    }
+
+    fn describe(&self, bag: &CubeSet) -> String {
+        (0..self.colours.len())
+            .filter(|colour| bag.count(*colour) > 0)
+            .map(|colour| format!("{} {}", bag.count(colour), self.colours.name(colour)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+// Estimating what's in the bag from the draws seen.  Each observation is taken to be
+// one handful drawn without replacement, with the cubes put back before the next, so
+// the chance of a handful is multivariate hypergeometric:
+//   P(k | K) = Π C(K_colour, k_colour) / C(N, n)
+// for a bag of N cubes (K_colour of each colour) and a handful of n (k_colour of each).
+#[allow(dead_code)]
+impl Games {
+    // The most likely bags of the given total size to have produced this game
+    fn estimate_game(&self, id: u32, total: u32) -> Option<Estimate> {
+        let game = self.games.iter().find(|game| game.id == id)?;
+        let observations: Vec<&CubeSet> = game.observations.iter().collect();
+        Estimate::most_likely(self.colours.len(), total, &observations)
+    }
+
+    // As estimate_game, but with every game assumed to use the same bag
+    fn estimate_pooled(&self, total: u32) -> Option<Estimate> {
+        let observations: Vec<&CubeSet> = self
+            .games
+            .iter()
+            .flat_map(|game| game.observations.iter())
+            .collect();
+        Estimate::most_likely(self.colours.len(), total, &observations)
+    }
+
+    fn estimate_each_game(&self, total: u32) -> Vec<(u32, Option<Estimate>)> {
+        self.games
+            .iter()
+            .map(|game| (game.id, self.estimate_game(game.id, total)))
+            .collect()
+    }
+
+    // Candidate bags, most likely first, with the log likelihood of each.  Bags that
+    // couldn't have produced the games come last with a log likelihood of -inf.
+    fn rank_bags(&self, candidates: Vec<CubeSet>) -> Vec<(CubeSet, f64)> {
+        let observations: Vec<&CubeSet> = self
+            .games
+            .iter()
+            .flat_map(|game| game.observations.iter())
+            .collect();
+        let mut ranked: Vec<(CubeSet, f64)> = candidates
+            .into_iter()
+            .map(|bag| {
+                let likelihood = log_likelihood(&bag, &observations);
+                (bag, likelihood)
+            })
+            .collect();
+        ranked.sort_by(|(_, l1), (_, l2)| l2.total_cmp(l1));
+        ranked
+    }
+}
+
+#[derive(Debug)]
+struct Estimate {
+    // Every bag that ties for the highest likelihood
+    bags: Vec<CubeSet>,
+    log_likelihood: f64,
+}
+
+impl Estimate {
+    fn most_likely(colours: usize, total: u32, observations: &[&CubeSet]) -> Option<Self> {
+        // Any bag has to hold at least as many of each colour as were ever drawn
+        let minimum = observations
+            .iter()
+            .fold(CubeSet::default(), |bag, obs| bag.max(obs));
+
+        let mut best: Option<Estimate> = None;
+        for bag in bags_of_size(total, colours, &minimum) {
+            let likelihood = log_likelihood(&bag, observations);
+            if likelihood == f64::NEG_INFINITY {
+                continue;
+            }
+            match &mut best {
+                Some(estimate) if (likelihood - estimate.log_likelihood).abs() < 1e-9 => {
+                    estimate.bags.push(bag)
+                }
+                Some(estimate) if likelihood < estimate.log_likelihood => {}
+                _ => {
+                    best = Some(Estimate {
+                        bags: vec![bag],
+                        log_likelihood: likelihood,
+                    })
+                }
+            }
+        }
+        best
+    }
+}
+
+// Every bag of exactly `total` cubes over the first `colours` colours that holds at
+// least `minimum` of each
+fn bags_of_size(total: u32, colours: usize, minimum: &CubeSet) -> Vec<CubeSet> {
+    let required: u32 = (0..colours).map(|colour| minimum.count(colour)).sum();
+    if colours == 0 || required > total {
+        return Vec::new();
+    }
+
+    let mut bags = Vec::new();
+    let mut counts: Vec<u32> = (0..colours).map(|colour| minimum.count(colour)).collect();
+    fill_bags(total - required, 0, &mut counts, &mut bags);
+    bags
+}
+
+fn fill_bags(spare: u32, colour: usize, counts: &mut Vec<u32>, bags: &mut Vec<CubeSet>) {
+    if colour == counts.len() - 1 {
+        counts[colour] += spare;
+        bags.push(CubeSet {
+            counts: counts.clone(),
+        });
+        counts[colour] -= spare;
+        return;
+    }
+    for extra in 0..=spare {
+        counts[colour] += extra;
+        fill_bags(spare - extra, colour + 1, counts, bags);
+        counts[colour] -= extra;
+    }
+}
+
+// Log of the chance of drawing exactly this handful from the bag
+fn log_observation_probability(bag: &CubeSet, observation: &CubeSet) -> f64 {
+    let colours = usize::max(bag.counts.len(), observation.counts.len());
+    let bag_size: u32 = bag.counts.iter().sum();
+    let handful: u32 = observation.counts.iter().sum();
+    (0..colours)
+        .map(|colour| ln_choose(bag.count(colour), observation.count(colour)))
+        .sum::<f64>()
+        - ln_choose(bag_size, handful)
+}
+
+fn log_likelihood(bag: &CubeSet, observations: &[&CubeSet]) -> f64 {
+    observations
+        .iter()
+        .map(|obs| log_observation_probability(bag, obs))
+        .sum()
+}
+
+// ln C(n, k), which is -inf when there's no way to choose k from n
+fn ln_choose(n: u32, k: u32) -> f64 {
+    if k > n {
+        return f64::NEG_INFINITY;
+    }
+    let k = u32::min(k, n - k);
+    (1..=k)
+        .map(|i| (f64::from(n - k + i) / f64::from(i)).ln())
+        .sum()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    const EXAMPLE: &str = "Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green\nGame 2: 1 blue, 2 green; 3 green, 4 blue, 1 red; 1 green, 1 blue\nGame 3: 8 green, 6 blue, 20 red; 5 blue, 4 red, 13 green; 5 green, 1 red\nGame 4: 1 green, 3 red, 6 blue; 3 green, 6 red; 3 green, 15 blue, 14 red\nGame 5: 6 red, 1 blue, 3 green; 2 blue, 1 red, 2 green";
+
     #[test]
     fn check_day02_part1_case1() {
         let game_line = "Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green";
-        let game = Game::new(game_line);
-        let true_bag = CubeSet::create_from_rgb(12, 13, 14);
+        let mut colours = ColourRegistry::default();
+        let game = Game::new(game_line, &mut colours);
+        let true_bag = CubeSet::create_from_rgb(&mut colours, 12, 13, 14);
         assert!(game.is_compatible_with(&true_bag));
     }
 
     #[test]
     fn check_day02_part1_case2() {
         let game_line = "Game 3: 8 green, 6 blue, 20 red; 5 blue, 4 red, 13 green; 5 green, 1 red";
-        let game = Game::new(game_line);
-        let true_bag = CubeSet::create_from_rgb(12, 13, 14);
+        let mut colours = ColourRegistry::default();
+        let game = Game::new(game_line, &mut colours);
+        let true_bag = CubeSet::create_from_rgb(&mut colours, 12, 13, 14);
         assert!(!game.is_compatible_with(&true_bag));
     }
 
     #[test]
     fn check_day02_part2_case1() {
         let game_line = "Game 2: 1 blue, 2 green; 3 green, 4 blue, 1 red; 1 green, 1 blue";
-        let game = Game::new(game_line);
-        assert_eq!(game.min_of_colour("red"), 1);
-        assert_eq!(game.min_of_colour("blue"), 4);
-        assert_eq!(game.min_of_colour("green"), 3);
+        let mut colours = ColourRegistry::default();
+        let game = Game::new(game_line, &mut colours);
+        assert_eq!(game.min_of_colour(colours.id("red").unwrap()), 1);
+        assert_eq!(game.min_of_colour(colours.id("blue").unwrap()), 4);
+        assert_eq!(game.min_of_colour(colours.id("green").unwrap()), 3);
     }
 
     #[test]
     fn check_day02_both_case1() {
         assert_eq!(day02(""), ("0".to_string(), "0".to_string()))
     }
+
+    #[test]
+    fn check_day02_example() {
+        assert_eq!(day02(EXAMPLE), ("8".to_string(), "2286".to_string()))
+    }
+
+    #[test]
+    fn check_unknown_colours() {
+        let games = Games::create("Game 1: 2 red, 1 yellow\nGame 2: 3 red; 2 blue");
+        let bag = games.bag(&[("red", 12), ("green", 13), ("blue", 14)]);
+        // Nothing in the bag is yellow, however many cubes it has in total
+        assert_eq!(games.possible_with(&bag), vec![2]);
+        assert_eq!(
+            games.describe(&games.minimal_bag()),
+            "3 red, 1 yellow, 2 blue"
+        );
+        assert_eq!(games.power(&games.minimal_bag(), &["red", "yellow"]), 3);
+        assert_eq!(games.power(&games.minimal_bag(), &["red", "green"]), 0);
+    }
+
+    #[test]
+    fn check_bag_queries() {
+        let games = Games::create(EXAMPLE);
+        assert_eq!(
+            games.describe(&games.minimal_bag()),
+            "15 blue, 20 red, 13 green"
+        );
+        let bag = games.minimal_bag_for(&[1, 2, 5]);
+        assert_eq!(games.describe(&bag), "6 blue, 6 red, 3 green");
+        assert_eq!(games.possible_with(&bag), vec![1, 2, 5]);
+        assert_eq!(games.power(&bag, &["red", "green", "blue"]), 108);
+    }
+
+    #[test]
+    fn check_observation_probability() {
+        let games = Games::create("Game 1: 1 red, 1 blue");
+        let bag = games.bag(&[("red", 3), ("blue", 2)]);
+        let observation = &games.games[0].observations[0];
+        assert!((log_observation_probability(&bag, observation).exp() - 0.6).abs() < 1e-12);
+
+        let too_few_blue = games.bag(&[("red", 3)]);
+        assert_eq!(
+            log_observation_probability(&too_few_blue, observation),
+            f64::NEG_INFINITY
+        );
+    }
+
+    #[test]
+    fn check_estimate_single_game() {
+        // Two reds in a handful of two from four cubes: most likely all four are red
+        let games = Games::create("Game 1: 2 red; 1 blue\nGame 2: 2 red");
+        let estimate = games.estimate_game(2, 4).unwrap();
+        assert_eq!(estimate.bags, vec![games.bag(&[("red", 4)])]);
+        assert_eq!(estimate.log_likelihood, 0.0);
+
+        // Game 1 also saw a blue, so it can't have been all red
+        let estimate = games.estimate_game(1, 4).unwrap();
+        assert_eq!(games.describe(&estimate.bags[0]), "3 red, 1 blue");
+
+        assert!(games.estimate_game(1, 2).is_none());
+        assert!(games.estimate_game(3, 4).is_none());
+    }
+
+    #[test]
+    fn check_estimate_pooled_and_ranked() {
+        let games = Games::create("Game 1: 1 red\nGame 2: 1 blue\nGame 3: 1 red");
+        let estimate = games.estimate_pooled(3).unwrap();
+        assert_eq!(estimate.bags, vec![games.bag(&[("red", 2), ("blue", 1)])]);
+        assert!((estimate.log_likelihood - (4.0_f64 / 27.0).ln()).abs() < 1e-12);
+
+        let per_game = games.estimate_each_game(2);
+        assert_eq!(per_game[1].0, 2);
+        assert_eq!(
+            per_game[1].1.as_ref().unwrap().bags,
+            vec![games.bag(&[("blue", 2)])]
+        );
+
+        let ranked = games.rank_bags(vec![
+            games.bag(&[("red", 1), ("blue", 2)]),
+            games.bag(&[("red", 3)]),
+            games.bag(&[("red", 2), ("blue", 1)]),
+        ]);
+        let order: Vec<String> = ranked.iter().map(|(bag, _)| games.describe(bag)).collect();
+        assert_eq!(order, vec!["2 red, 1 blue", "1 red, 2 blue", "3 red"]);
+        assert_eq!(ranked[2].1, f64::NEG_INFINITY);
+    }
 }
@@ -1,5 +1,6 @@
 pub fn day03(input_lines: &str) -> (String, String) {
     let schematic = Schematic::create(input_lines);
+
     let answer1 = schematic.sum_part_numbers();
     let answer2 = schematic.gear_ratio();
     (format!("{}", answer1), format!("{}", answer2))
@@ -8,6 +9,20 @@ pub fn day03(input_lines: &str) -> (String, String) {
 struct Schematic {
     symbols: Vec<Symbol>,
     numbers: Vec<Number>,
+    // What's in each cell of the grid, row by row, so adjacency checks only have to
+    // look at the cells around an item rather than at every other item
+    cells: Vec<Option<Occupant>>,
+    width: usize,
+    // Length of each line of the input and what ended it ("\n", "\r\n", or nothing for
+    // a last line without one), for rendering it back out
+    row_widths: Vec<usize>,
+    line_endings: Vec<&'static str>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Occupant {
+    Number(usize),
+    Symbol(usize),
 }
 
 impl Schematic {
@@ -32,59 +47,367 @@ impl Schematic {
                         // Symbol
                         symbols.push(Symbol {
                             location: Point::new(i, j),
+                            kind: token,
                         });
                     }
                 }
             }
             // Numbers don't wrap over lines, so any number has now ended
             if let Some(builder) = number_builder {
-                numbers.push(builder.build(Point::new(line.len(), j)));
+                numbers.push(builder.build(Point::new(line.chars().count() - 1, j)));
                 number_builder = None;
             }
         }
-        Schematic { symbols, numbers }
+
+        let width = input_lines
+            .lines()
+            .map(|line| line.chars().count())
+            .max()
+            .unwrap_or(0);
+        let height = input_lines.lines().count();
+        let mut cells = vec![None; width * height];
+        for (id, number) in numbers.iter().enumerate() {
+            let HorizontalLine { left, right } = &number.location;
+            for x in left.x..=right.x {
+                cells[Self::cell_index(width, x, left.y)] = Some(Occupant::Number(id));
+            }
+        }
+        for (id, symbol) in symbols.iter().enumerate() {
+            let Point { x, y } = symbol.location;
+            cells[Self::cell_index(width, x, y)] = Some(Occupant::Symbol(id));
+        }
+
+        Schematic {
+            symbols,
+            numbers,
+            cells,
+            width,
+            row_widths: input_lines
+                .lines()
+                .map(|line| line.chars().count())
+                .collect(),
+            line_endings: input_lines
+                .split_inclusive('\n')
+                .map(|line| {
+                    if line.ends_with("\r\n") {
+                        "\r\n"
+                    } else if line.ends_with('\n') {
+                        "\n"
+                    } else {
+                        ""
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    fn cell_index(width: usize, x: i32, y: i32) -> usize {
+        usize::try_from(y).unwrap() * width + usize::try_from(x).unwrap()
+    }
+
+    fn occupant(&self, point: &Point) -> Option<Occupant> {
+        if point.x < 0 || point.y < 0 || point.x >= self.width.try_into().unwrap() {
+            return None;
+        }
+        *self
+            .cells
+            .get(Self::cell_index(self.width, point.x, point.y))?
+    }
+
+    // Everything in the ring of cells around the line, each number only once
+    fn occupants_around(&self, line: &HorizontalLine) -> Vec<Occupant> {
+        let mut occupants: Vec<Occupant> = line
+            .perimeter()
+            .filter_map(|point| self.occupant(&point))
+            .collect();
+        occupants.sort_unstable_by_key(|occupant| match occupant {
+            Occupant::Number(id) => (0, *id),
+            Occupant::Symbol(id) => (1, *id),
+        });
+        occupants.dedup();
+        occupants
+    }
+
+    fn numbers_around(&self, symbol: &Symbol) -> Vec<&Number> {
+        let area = HorizontalLine {
+            left: symbol.location,
+            right: symbol.location,
+        };
+        self.occupants_around(&area)
+            .into_iter()
+            .filter_map(|occupant| match occupant {
+                Occupant::Number(id) => Some(&self.numbers[id]),
+                Occupant::Symbol(_) => None,
+            })
+            .collect()
     }
 
     fn sum_part_numbers(&self) -> u32 {
         self.numbers
             .iter()
             .filter(|number| {
-                self.symbols
+                self.occupants_around(&number.location)
                     .iter()
-                    .any(|symbol| number.location.is_point_adjacent_to(&symbol.location))
+                    .any(|occupant| matches!(occupant, Occupant::Symbol(_)))
             })
             .map(|number| number.value)
             .sum()
     }
 
-    fn gear_ratio(&self) -> u32 {
+    fn gear_ratio(&self) -> u64 {
+        self.sum_gears(&GearRule::standard())
+    }
+
+    // Total over all symbols that match the rule of their reduced adjacent numbers
+    fn sum_gears(&self, rule: &GearRule) -> u64 {
         self.symbols
             .iter()
-            .map(|symbol| {
-                // Get adjacent numbers
-                self.numbers
-                    .iter()
-                    .filter(|number| number.location.is_point_adjacent_to(&symbol.location))
-                    .collect()
+            .filter(|symbol| rule.kind.is_none_or(|kind| symbol.kind == kind))
+            .map(|symbol| self.numbers_around(symbol))
+            .filter(|adjacent_numbers| adjacent_numbers.len() == rule.adjacent_numbers)
+            .map(|adjacent_numbers| {
+                rule.reducer
+                    .reduce(adjacent_numbers.iter().map(|num| num.value.into()))
             })
-            .filter(|adjacent_numbers: &Vec<&Number>| {
-                adjacent_numbers.len() == 2 // Gears only
+            .sum()
+    }
+}
+
+// Queries for poking around a schematic beyond the puzzle answers
+#[allow(dead_code)]
+impl Schematic {
+    // Numbers next to at least one symbol of this kind, e.g. '#'
+    fn numbers_adjacent_to_kind(&self, kind: char) -> Vec<&Number> {
+        self.numbers
+            .iter()
+            .filter(|number| {
+                self.occupants_around(&number.location)
+                    .iter()
+                    .any(|occupant| {
+                        matches!(occupant, Occupant::Symbol(id) if self.symbols[*id].kind == kind)
+                    })
             })
-            .map(|adjacent_numbers| {
-                // Calculate gear ratio for this symbol
-                adjacent_numbers
+            .collect()
+    }
+
+    fn symbols_with_adjacent_count(&self, count: usize) -> Vec<&Symbol> {
+        self.symbols
+            .iter()
+            .filter(|symbol| self.numbers_around(symbol).len() == count)
+            .collect()
+    }
+
+    // Numbers next to no symbol, and symbols next to no number
+    fn diagnostics(&self) -> Diagnostics<'_> {
+        let lonely_numbers = self
+            .numbers
+            .iter()
+            .filter(|number| {
+                !self
+                    .occupants_around(&number.location)
                     .iter()
-                    .map(|num| num.value)
-                    .product::<u32>()
+                    .any(|occupant| matches!(occupant, Occupant::Symbol(_)))
             })
-            .sum()
+            .collect();
+        Diagnostics {
+            lonely_numbers,
+            isolated_symbols: self.symbols_with_adjacent_count(0),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[allow(dead_code)]
+enum RenderStyle {
+    // Exactly the original input
+    Plain,
+    Ansi,
+    Html,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum CellClass {
+    Blank,
+    PartNumber,
+    OtherNumber,
+    Gear,
+    OtherSymbol,
+}
+
+impl CellClass {
+    fn ansi_colour(&self) -> Option<&'static str> {
+        match self {
+            Self::Blank => None,
+            Self::PartNumber => Some("\x1b[32m"),
+            Self::OtherNumber => Some("\x1b[31m"),
+            Self::Gear => Some("\x1b[1;33m"),
+            Self::OtherSymbol => Some("\x1b[36m"),
+        }
+    }
+
+    fn html_class(&self) -> Option<&'static str> {
+        match self {
+            Self::Blank => None,
+            Self::PartNumber => Some("part"),
+            Self::OtherNumber => Some("other"),
+            Self::Gear => Some("gear"),
+            Self::OtherSymbol => Some("symbol"),
+        }
+    }
+}
+
+impl Schematic {
+    // Rebuilds the grid from the parsed numbers and symbols, rather than from the
+    // input, so that any mistakes in where items were placed show up
+    #[allow(dead_code)]
+    fn render(&self, style: RenderStyle) -> String {
+        let mut rows: Vec<Vec<(char, CellClass)>> = self
+            .row_widths
+            .iter()
+            .map(|width| vec![('.', CellClass::Blank); *width])
+            .collect();
+        let mut place = |point: &Point, value: (char, CellClass)| {
+            rows[usize::try_from(point.y).unwrap()][usize::try_from(point.x).unwrap()] = value;
+        };
+
+        for number in &self.numbers {
+            let is_part = self
+                .occupants_around(&number.location)
+                .iter()
+                .any(|occupant| matches!(occupant, Occupant::Symbol(_)));
+            let class = if is_part {
+                CellClass::PartNumber
+            } else {
+                CellClass::OtherNumber
+            };
+            for (offset, digit) in number.text.chars().enumerate() {
+                let point = Point {
+                    x: number.location.left.x + i32::try_from(offset).unwrap(),
+                    y: number.location.left.y,
+                };
+                place(&point, (digit, class));
+            }
+        }
+        let gear_rule = GearRule::standard();
+        for symbol in &self.symbols {
+            let is_gear = gear_rule.kind == Some(symbol.kind)
+                && self.numbers_around(symbol).len() == gear_rule.adjacent_numbers;
+            let class = if is_gear {
+                CellClass::Gear
+            } else {
+                CellClass::OtherSymbol
+            };
+            place(&symbol.location, (symbol.kind, class));
+        }
+
+        let mut output = String::new();
+        if style == RenderStyle::Html {
+            output += "<!DOCTYPE html>\n<html>\n<head>\n<style>\n";
+            output += "pre { background: #0f0f23; color: #666; }\n";
+            output += ".part { color: #00cc00; }\n.other { color: #ff4444; }\n";
+            output += ".gear { color: #ffff66; font-weight: bold; }\n.symbol { color: #66ccff; }\n";
+            output += "</style>\n</head>\n<body>\n<pre>\n";
+        }
+
+        for (j, row) in rows.iter().enumerate() {
+            let mut current = CellClass::Blank;
+            for (c, class) in row {
+                if *class != current {
+                    output += &Self::end_class(current, style);
+                    output += &Self::start_class(*class, style);
+                    current = *class;
+                }
+                match (style, c) {
+                    (RenderStyle::Html, '<') => output += "&lt;",
+                    (RenderStyle::Html, '>') => output += "&gt;",
+                    (RenderStyle::Html, '&') => output += "&amp;",
+                    _ => output.push(*c),
+                }
+            }
+            output += &Self::end_class(current, style);
+            if style == RenderStyle::Plain {
+                output += self.line_endings[j];
+            } else {
+                output.push('\n');
+            }
+        }
+
+        if style == RenderStyle::Html {
+            output += "</pre>\n</body>\n</html>\n";
+        }
+        output
+    }
+
+    fn start_class(class: CellClass, style: RenderStyle) -> String {
+        match style {
+            RenderStyle::Plain => String::new(),
+            RenderStyle::Ansi => class.ansi_colour().unwrap_or_default().to_string(),
+            RenderStyle::Html => class
+                .html_class()
+                .map_or(String::new(), |name| format!("<span class=\"{}\">", name)),
+        }
+    }
+
+    fn end_class(class: CellClass, style: RenderStyle) -> String {
+        match (style, class) {
+            (_, CellClass::Blank) | (RenderStyle::Plain, _) => String::new(),
+            (RenderStyle::Ansi, _) => String::from("\x1b[0m"),
+            (RenderStyle::Html, _) => String::from("</span>"),
+        }
+    }
+}
+
+#[allow(dead_code)]
+struct Diagnostics<'a> {
+    lonely_numbers: Vec<&'a Number>,
+    isolated_symbols: Vec<&'a Symbol>,
+}
+
+// Which symbols count as gears, and how to combine the numbers around one
+struct GearRule {
+    // Any symbol if None
+    kind: Option<char>,
+    adjacent_numbers: usize,
+    reducer: Reducer,
+}
+
+impl GearRule {
+    // A '*' next to exactly two numbers, which are multiplied together
+    fn standard() -> Self {
+        Self {
+            kind: Some('*'),
+            adjacent_numbers: 2,
+            reducer: Reducer::Product,
+        }
+    }
+}
+
+#[allow(dead_code)]
+enum Reducer {
+    Product,
+    Sum,
+    Max,
+}
+
+impl Reducer {
+    fn reduce<I>(&self, values: I) -> u64
+    where
+        I: Iterator<Item = u64>,
+    {
+        match self {
+            Self::Product => values.product(),
+            Self::Sum => values.sum(),
+            Self::Max => values.max().unwrap_or(0),
+        }
     }
 }
 
 struct Symbol {
     location: Point,
+    kind: char,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 struct Point {
     x: i32,
     y: i32,
@@ -101,6 +424,8 @@ impl Point {
 
 struct Number {
     value: u32,
+    // As written, which may have leading zeros
+    text: String,
     location: HorizontalLine,
 }
 
@@ -120,6 +445,7 @@ impl NumberBuilder {
     fn build(self, right: Point) -> Number {
         Number {
             value: self.number_buf.parse().unwrap(),
+            text: self.number_buf,
             location: HorizontalLine {
                 left: self.left,
                 right,
@@ -147,9 +473,14 @@ impl HorizontalLine {
         )
     }
 
-    fn is_point_adjacent_to(&self, point: &Point) -> bool {
+    // Points around the outside of the line, including the diagonals
+    fn perimeter(&self) -> impl Iterator<Item = Point> + '_ {
         let (area_l, area_r) = self.get_adjacent_area();
-        point.x >= area_l.x && point.x <= area_r.x && point.y >= area_l.y && point.y <= area_r.y
+        (area_l.y..=area_r.y)
+            .flat_map(move |y| (area_l.x..=area_r.x).map(move |x| Point { x, y }))
+            .filter(|point| {
+                point.y != self.left.y || point.x < self.left.x || point.x > self.right.x
+            })
     }
 }
 
@@ -170,4 +501,178 @@ mod tests {
         let schematic = Schematic::create(input_lines);
         assert_eq!(schematic.gear_ratio(), 467835);
     }
+
+    #[test]
+    fn check_only_stars_are_gears() {
+        let schematic = Schematic::create("2#3\n...\n4*5");
+        assert_eq!(schematic.gear_ratio(), 20);
+
+        let any_symbol = GearRule {
+            kind: None,
+            ..GearRule::standard()
+        };
+        assert_eq!(schematic.sum_gears(&any_symbol), 26);
+    }
+
+    #[test]
+    fn check_symbol_queries() {
+        let input_lines = "467..114..\n...*......\n..35..633.\n......#...\n617*......\n.....+.58.\n..592.....\n......755.\n...$.*....\n.664.598..";
+        let schematic = Schematic::create(input_lines);
+
+        let values = |numbers: Vec<&Number>| numbers.iter().map(|n| n.value).collect::<Vec<_>>();
+        assert_eq!(values(schematic.numbers_adjacent_to_kind('#')), vec![633]);
+        assert_eq!(
+            values(schematic.numbers_adjacent_to_kind('*')),
+            vec![467, 35, 617, 755, 598]
+        );
+
+        let kinds = |symbols: Vec<&Symbol>| symbols.iter().map(|s| s.kind).collect::<String>();
+        assert_eq!(kinds(schematic.symbols_with_adjacent_count(1)), "#*+$");
+        assert_eq!(kinds(schematic.symbols_with_adjacent_count(2)), "**");
+
+        let sum_rule = GearRule {
+            kind: Some('*'),
+            adjacent_numbers: 2,
+            reducer: Reducer::Sum,
+        };
+        assert_eq!(schematic.sum_gears(&sum_rule), 467 + 35 + 755 + 598);
+        let max_rule = GearRule {
+            kind: None,
+            adjacent_numbers: 1,
+            reducer: Reducer::Max,
+        };
+        assert_eq!(schematic.sum_gears(&max_rule), 633 + 617 + 592 + 664);
+
+        let diagnostics = schematic.diagnostics();
+        assert_eq!(values(diagnostics.lonely_numbers), vec![114, 58]);
+        assert!(diagnostics.isolated_symbols.is_empty());
+    }
+
+    #[test]
+    fn check_diagnostics() {
+        let schematic = Schematic::create("1....\n..%..\n....2\n3&...");
+        let diagnostics = schematic.diagnostics();
+        assert_eq!(diagnostics.lonely_numbers.len(), 2);
+        assert_eq!(diagnostics.lonely_numbers[0].value, 1);
+        assert_eq!(diagnostics.lonely_numbers[1].value, 2);
+        assert_eq!(diagnostics.isolated_symbols.len(), 1);
+        assert_eq!(diagnostics.isolated_symbols[0].kind, '%');
+    }
+
+    #[test]
+    fn check_render_round_trip() {
+        for input_lines in [
+            "467..114..\n...*......\n..35..633.\n......#...\n617*......\n.....+.58.\n..592.....\n......755.\n...$.*....\n.664.598..",
+            "..12\n...*\n..7.\n",
+            "007.\n\n.<&>9\n*",
+            "1*\r\n..",
+            "4..\r\n.#.\n..2\r\n",
+            "",
+        ] {
+            let schematic = Schematic::create(input_lines);
+            assert_eq!(schematic.render(RenderStyle::Plain), input_lines);
+        }
+    }
+
+    #[test]
+    fn check_render_highlights() {
+        let schematic = Schematic::create("12*3.\n....4\n&<..5");
+        assert_eq!(
+            schematic.render(RenderStyle::Ansi),
+            "\x1b[32m12\x1b[0m\x1b[1;33m*\x1b[0m\x1b[32m3\x1b[0m.\n\
+             ....\x1b[31m4\x1b[0m\n\
+             \x1b[36m&<\x1b[0m..\x1b[31m5\x1b[0m\n"
+        );
+        let html = schematic.render(RenderStyle::Html);
+        assert!(html.contains(
+            "<pre>\n<span class=\"part\">12</span><span class=\"gear\">*</span><span class=\"part\">3</span>.\n"
+        ));
+        assert!(html.contains(
+            "<span class=\"symbol\">&amp;&lt;</span>..<span class=\"other\">5</span>\n</pre>"
+        ));
+    }
+
+    #[test]
+    fn check_number_at_end_of_line() {
+        let schematic = Schematic::create("..12\n...*\n..7.");
+        assert_eq!(schematic.numbers[0].location.right, Point::new(3, 0));
+        assert_eq!(schematic.sum_part_numbers(), 19);
+        assert_eq!(schematic.gear_ratio(), 84);
+    }
+
+    // The original all-pairs checks, to compare the grid index against
+    fn is_point_adjacent_to(line: &HorizontalLine, point: &Point) -> bool {
+        let (area_l, area_r) = line.get_adjacent_area();
+        point.x >= area_l.x && point.x <= area_r.x && point.y >= area_l.y && point.y <= area_r.y
+    }
+
+    fn brute_force_sum_part_numbers(schematic: &Schematic) -> u32 {
+        schematic
+            .numbers
+            .iter()
+            .filter(|number| {
+                schematic
+                    .symbols
+                    .iter()
+                    .any(|symbol| is_point_adjacent_to(&number.location, &symbol.location))
+            })
+            .map(|number| number.value)
+            .sum()
+    }
+
+    fn brute_force_gear_ratio(schematic: &Schematic) -> u64 {
+        schematic
+            .symbols
+            .iter()
+            .filter(|symbol| symbol.kind == '*')
+            .map(|symbol| {
+                schematic
+                    .numbers
+                    .iter()
+                    .filter(|number| is_point_adjacent_to(&number.location, &symbol.location))
+                    .map(|number| u64::from(number.value))
+                    .collect::<Vec<u64>>()
+            })
+            .filter(|adjacent| adjacent.len() == 2)
+            .map(|adjacent| adjacent.iter().product::<u64>())
+            .sum()
+    }
+
+    #[test]
+    fn check_index_matches_brute_force() {
+        // Pseudo-random schematic from a linear congruential generator
+        let mut seed: u64 = 2023;
+        let mut input = String::new();
+        for _ in 0..120 {
+            for _ in 0..120 {
+                seed = seed
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                input.push(match (seed >> 33) % 10 {
+                    0..=4 => '.',
+                    5..=7 => char::from(b'0' + ((seed >> 40) % 10) as u8),
+                    8 => '*',
+                    _ => '#',
+                });
+            }
+            input.push('\n');
+        }
+        let schematic = Schematic::create(&input);
+        assert_eq!(
+            schematic.sum_part_numbers(),
+            brute_force_sum_part_numbers(&schematic)
+        );
+        assert_eq!(schematic.gear_ratio(), brute_force_gear_ratio(&schematic));
+    }
+
+    #[test]
+    fn check_large_schematic() {
+        // 2000x2000, made of 4x2 tiles that each hold one gear
+        let row = "2*3.".repeat(500);
+        let blank = ".".repeat(2000);
+        let input = format!("{}\n{}\n", row, blank).repeat(1000);
+        let schematic = Schematic::create(&input);
+        assert_eq!(schematic.sum_part_numbers(), 500 * 1000 * 5);
+        assert_eq!(schematic.gear_ratio(), 500 * 1000 * 6);
+    }
 }
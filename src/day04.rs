@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 
 pub fn day04(input_lines: &str) -> (String, String) {
     let cards: Vec<Card> = input_lines.lines().map(Card::create).collect();
@@ -7,38 +7,204 @@ pub fn day04(input_lines: &str) -> (String, String) {
     (format!("{}", answer1), format!("{}", answer2))
 }
 
-fn part1(cards: &[Card]) -> u32 {
-    cards.iter().map(|card| card.points()).sum()
+fn part1(cards: &[Card]) -> u64 {
+    total_points(cards, &Rules::standard().points)
 }
 
-fn part2(cards: &Vec<Card>) -> u32 {
-    let mut cards_set = HashMap::new();
-    for card in cards {
-        cards_set.insert(card.id, card.clone());
+fn part2(cards: &[Card]) -> u128 {
+    cascade(cards, &Rules::standard().awards)
+        .expect("Too many cards!")
+        .total
+}
+
+// How a card scores points, and which cards it wins copies of, for a given number of
+// matches
+struct Rules {
+    points: PointsRule,
+    awards: AwardRule,
+}
+
+impl Rules {
+    fn standard() -> Self {
+        Self {
+            points: PointsRule::Exponential { base: 2 },
+            awards: AwardRule::NextN,
+        }
+    }
+
+    #[allow(dead_code)]
+    fn evaluate(&self, cards: &[Card]) -> Result<(u64, u128), CascadeError> {
+        Ok((
+            total_points(cards, &self.points),
+            cascade(cards, &self.awards)?.total,
+        ))
     }
+}
 
-    let mut cards_won: HashMap<u32, u32> = HashMap::new();
+#[allow(dead_code)]
+enum PointsRule {
+    // per_match points for each match
+    Linear { per_match: u64 },
+    // 1 for the first match, multiplied by base for each match after that
+    Exponential { base: u64 },
+    // Points indexed by number of matches; more matches than the table covers score
+    // the last entry, and an empty table scores nothing
+    Table(Vec<u64>),
+    Custom(Box<dyn Fn(u32) -> u64>),
+}
+
+impl PointsRule {
+    fn score(&self, matches: u32) -> u64 {
+        match self {
+            Self::Linear { per_match } => per_match * u64::from(matches),
+            Self::Exponential { base } => {
+                if matches == 0 {
+                    0
+                } else {
+                    base.checked_pow(matches - 1).expect("Points overflowed")
+                }
+            }
+            Self::Table(table) => {
+                let index: usize = matches.try_into().unwrap();
+                table
+                    .get(index)
+                    .or_else(|| table.last())
+                    .copied()
+                    .unwrap_or(0)
+            }
+            Self::Custom(score) => score(matches),
+        }
+    }
+}
+
+#[allow(dead_code)]
+enum AwardRule {
+    // The next N cards, up to the end of the table
+    NextN,
+    // The previous N cards, back to the start of the table
+    PreviousN,
+    // The next N cards, going back round to the start after the end of the table
+    WrapAround,
+}
 
-    for card in cards {
-        // Record original card
-        let copies_of_this_card: u32 = *cards_won.get(&card.id).unwrap_or(&0) + 1;
-        cards_won.insert(card.id, copies_of_this_card);
-        for won_card_id in card.cards_won() {
-            cards_won.insert(
-                won_card_id,
-                cards_won.get(&won_card_id).unwrap_or(&0) + copies_of_this_card,
-            );
+impl AwardRule {
+    // Positions in the table of the cards won by the card at `position`.  The same card
+    // can be won more than once if wrapping round a short table.
+    fn won_positions(&self, position: usize, matches: usize, table_len: usize) -> Vec<usize> {
+        match self {
+            Self::NextN => (position + 1..=position + matches)
+                .take_while(|won| *won < table_len)
+                .collect(),
+            Self::PreviousN => (position.saturating_sub(matches)..position).rev().collect(),
+            Self::WrapAround => (1..=matches)
+                .map(|offset| (position + offset) % table_len)
+                .collect(),
         }
     }
+}
+
+fn total_points(cards: &[Card], rule: &PointsRule) -> u64 {
+    cards
+        .iter()
+        .map(|card| rule.score(card.count_matches()))
+        .sum()
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum CascadeError {
+    // The number of copies of this card doesn't fit in a u128
+    Overflow { card: u32 },
+    // This card wins copies of itself, directly or through other cards, so never stops
+    Unbounded { card: u32 },
+}
 
-    cards_won.values().sum()
+// How many copies of each card ended up being scratched, and where they came from
+struct CascadeTrace {
+    // In table order, including the original card
+    copies: Vec<(u32, u128)>,
+    // Won card id -> (source card id, copies won from it)
+    sources: HashMap<u32, Vec<(u32, u128)>>,
+    total: u128,
+}
+
+impl CascadeTrace {
+    #[allow(dead_code)]
+    fn copies_of(&self, id: u32) -> Option<u128> {
+        self.copies
+            .iter()
+            .find(|(card, _)| *card == id)
+            .map(|(_, copies)| *copies)
+    }
+
+    #[allow(dead_code)]
+    fn sources_of(&self, id: u32) -> &[(u32, u128)] {
+        self.sources.get(&id).map_or(&[], Vec::as_slice)
+    }
+}
+
+fn cascade(cards: &[Card], rule: &AwardRule) -> Result<CascadeTrace, CascadeError> {
+    let won_by: Vec<Vec<usize>> = cards
+        .iter()
+        .enumerate()
+        .map(|(i, card)| {
+            rule.won_positions(i, card.count_matches().try_into().unwrap(), cards.len())
+        })
+        .collect();
+
+    // Work through the cards so that every card comes after all the cards that win it,
+    // at which point its number of copies is final
+    let mut times_won = vec![0; cards.len()];
+    for won in won_by.iter().flatten() {
+        times_won[*won] += 1;
+    }
+    let mut ready: VecDeque<usize> = (0..cards.len()).filter(|i| times_won[*i] == 0).collect();
+
+    let mut copies: Vec<u128> = vec![1; cards.len()];
+    let mut sources: HashMap<u32, Vec<(u32, u128)>> = HashMap::new();
+    let mut finished = 0;
+    while let Some(i) = ready.pop_front() {
+        finished += 1;
+        let copies_of_this_card = copies[i];
+        for &won in &won_by[i] {
+            let won_card_id = cards[won].id;
+            copies[won] = copies[won]
+                .checked_add(copies_of_this_card)
+                .ok_or(CascadeError::Overflow { card: won_card_id })?;
+            sources
+                .entry(won_card_id)
+                .or_default()
+                .push((cards[i].id, copies_of_this_card));
+            times_won[won] -= 1;
+            if times_won[won] == 0 {
+                ready.push_back(won);
+            }
+        }
+    }
+    if finished < cards.len() {
+        let stuck = (0..cards.len()).find(|i| times_won[*i] > 0).unwrap();
+        return Err(CascadeError::Unbounded {
+            card: cards[stuck].id,
+        });
+    }
+
+    let total = copies
+        .iter()
+        .try_fold(0_u128, |total, n| total.checked_add(*n))
+        .ok_or(CascadeError::Overflow {
+            card: cards.last().map_or(0, |card| card.id),
+        })?;
+    Ok(CascadeTrace {
+        copies: cards.iter().map(|card| card.id).zip(copies).collect(),
+        sources,
+        total,
+    })
 }
 
 #[derive(Clone)]
 struct Card {
     id: u32,
     choices: Vec<u32>,
-    winners: Vec<u32>,
+    winners: HashSet<u32>,
 }
 
 impl Card {
@@ -50,7 +216,9 @@ impl Card {
             .unwrap();
         let mut number_lists = initial_split.next().unwrap().split('|');
         let choices = Self::numbers_from_list(number_lists.next().unwrap());
-        let winners = Self::numbers_from_list(number_lists.next().unwrap());
+        let winners = Self::numbers_from_list(number_lists.next().unwrap())
+            .into_iter()
+            .collect();
         Card {
             id,
             choices,
@@ -75,19 +243,6 @@ impl Card {
             .try_into()
             .unwrap()
     }
-
-    fn points(&self) -> u32 {
-        let matches = self.count_matches();
-        if matches == 0 {
-            0
-        } else {
-            2_u32.pow(matches - 1)
-        }
-    }
-
-    fn cards_won(&self) -> Vec<u32> {
-        (0..self.count_matches()).map(|i| self.id + i + 1).collect()
-    }
 }
 
 #[cfg(test)]
@@ -108,9 +263,94 @@ mod tests {
         assert_eq!(part2(&cards), 30)
     }
 
+    #[test]
+    fn check_cascade_trace() {
+        let input = "Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53\nCard 2: 13 32 20 16 61 | 61 30 68 82 17 32 24 19\nCard 3:  1 21 53 59 44 | 69 82 63 72 16 21 14  1\nCard 4: 41 92 73 84 69 | 59 84 76 51 58  5 54 83\nCard 5: 87 83 26 28 32 | 88 30 70 12 93 22 82 36\nCard 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11";
+        let cards: Vec<Card> = input.lines().map(Card::create).collect();
+        let trace = cascade(&cards, &AwardRule::NextN).unwrap();
+        assert_eq!(
+            trace.copies,
+            vec![(1, 1), (2, 2), (3, 4), (4, 8), (5, 14), (6, 1)]
+        );
+        assert_eq!(trace.copies_of(5), Some(14));
+        assert_eq!(trace.sources_of(4), &[(1, 1), (2, 2), (3, 4)]);
+        assert!(trace.sources_of(1).is_empty());
+    }
+
+    #[test]
+    fn check_cascade_overflow() {
+        // Every card wins the next five, so copies grow roughly as 1.97^n
+        let cards: Vec<Card> = (1..=200)
+            .map(|id| Card::create(&format!("Card {}: 1 2 3 4 5 | 1 2 3 4 5", id)))
+            .collect();
+        assert!(matches!(
+            cascade(&cards[..100], &AwardRule::NextN),
+            Ok(CascadeTrace { total, .. }) if total > u128::from(u64::MAX)
+        ));
+        assert!(matches!(
+            cascade(&cards, &AwardRule::NextN),
+            Err(CascadeError::Overflow { .. })
+        ));
+    }
+
     #[test]
     fn check_won_cards() {
-        let card1 = Card::create("Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53");
-        assert_eq!(card1.cards_won(), vec![2, 3, 4, 5]);
+        // Card 1 of 6 has four matches
+        assert_eq!(AwardRule::NextN.won_positions(0, 4, 6), vec![1, 2, 3, 4]);
+        assert_eq!(AwardRule::NextN.won_positions(3, 4, 6), vec![4, 5]);
+        assert_eq!(AwardRule::PreviousN.won_positions(3, 4, 6), vec![2, 1, 0]);
+        assert_eq!(
+            AwardRule::WrapAround.won_positions(3, 4, 6),
+            vec![4, 5, 0, 1]
+        );
+        assert_eq!(
+            AwardRule::WrapAround.won_positions(1, 4, 2),
+            vec![0, 1, 0, 1]
+        );
+    }
+
+    #[test]
+    fn check_points_rules() {
+        assert_eq!(PointsRule::Linear { per_match: 3 }.score(4), 12);
+        assert_eq!(PointsRule::Exponential { base: 2 }.score(0), 0);
+        assert_eq!(PointsRule::Exponential { base: 3 }.score(4), 27);
+        let table = PointsRule::Table(vec![0, 1, 5, 10]);
+        assert_eq!(table.score(2), 5);
+        assert_eq!(table.score(7), 10);
+        assert_eq!(PointsRule::Table(vec![]).score(3), 0);
+        assert_eq!(
+            PointsRule::Custom(Box::new(|m| u64::from(m * m))).score(5),
+            25
+        );
+    }
+
+    #[test]
+    fn check_rule_combinations() {
+        let input = "Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53\nCard 2: 13 32 20 16 61 | 61 30 68 82 17 32 24 19\nCard 3:  1 21 53 59 44 | 69 82 63 72 16 21 14  1\nCard 4: 41 92 73 84 69 | 59 84 76 51 58  5 54 83\nCard 5: 87 83 26 28 32 | 88 30 70 12 93 22 82 36\nCard 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11";
+        let cards: Vec<Card> = input.lines().map(Card::create).collect();
+        // Matches per card: 4, 2, 2, 1, 0, 0
+
+        let standard = Rules::standard();
+        assert_eq!(standard.evaluate(&cards), Ok((13, 30)));
+
+        let linear_previous = Rules {
+            points: PointsRule::Linear { per_match: 1 },
+            awards: AwardRule::PreviousN,
+        };
+        // Card 4 wins card 3; card 3 wins 2 and 1; card 2 wins 1.  So 1 + 2 + 3 + 6 copies of
+        // cards 4 down to 1, plus cards 5 and 6
+        assert_eq!(linear_previous.evaluate(&cards), Ok((9, 14)));
+        let trace = cascade(&cards, &AwardRule::PreviousN).unwrap();
+        assert_eq!(trace.sources_of(1), &[(3, 2), (2, 3)]);
+
+        // On a table of just the first two cards, card 1 wins copies of itself
+        let wrap = Rules {
+            points: PointsRule::Table(vec![0, 100]),
+            awards: AwardRule::WrapAround,
+        };
+        assert_eq!(
+            wrap.evaluate(&cards[..2]),
+            Err(CascadeError::Unbounded { card: 1 })
+        );
     }
 }
@@ -1,3 +1,6 @@
+use itertools::Itertools;
+use std::collections::HashMap;
+use std::fmt;
 use std::ops::Range;
 
 pub fn day05(input_lines: &str) -> (String, String) {
@@ -7,53 +10,109 @@ pub fn day05(input_lines: &str) -> (String, String) {
         .map(|n| n.parse::<i64>().unwrap())
         .collect();
     lines.next();
-    let mappings = Mappings::create(lines);
+    let mappings =
+        Mappings::create(lines, 3).unwrap_or_else(|e| panic!("Can't read the almanac: {}", e));
     let answer1 = part1(&seeds, &mappings);
     let answer2 = part2(&seeds, &mappings);
     (format!("{}", answer1), format!("{}", answer2))
 }
 
 fn part1(seeds: &[i64], mappings: &Mappings) -> i64 {
-    seeds.iter().map(|seed| mappings.map(*seed)).min().unwrap()
+    let chain = mappings.composed();
+    seeds.iter().map(|seed| chain.map(*seed)).min().unwrap()
 }
 
 fn part2(seed_numbers: &[i64], mappings: &Mappings) -> i64 {
-    let mut ranges: Vec<Range<i64>> = Vec::new();
+    let chain = mappings.composed();
+    seed_ranges(seed_numbers)
+        .into_iter()
+        .flat_map(|range| chain.map_range(range))
+        .map(|range| range.start)
+        .min()
+        .unwrap()
+}
 
-    for i in (0..seed_numbers.len()).step_by(2) {
-        let start = seed_numbers[i];
-        let len = seed_numbers[i + 1];
-        ranges.push(start..(start + len));
-    }
+// Seed numbers come in pairs of range start and range length
+fn seed_ranges(seed_numbers: &[i64]) -> Vec<Range<i64>> {
+    seed_numbers
+        .chunks(2)
+        .map(|pair| pair[0]..(pair[0] + pair[1]))
+        .collect()
+}
 
-    let mut min_location = 0;
-    loop {
-        if min_location % 1000000 == 0 {
-            println!("Trying location: {}", min_location);
-        }
-        let input = mappings.reverse_map(min_location);
-        if ranges.iter().any(|range| range.contains(&input)) {
-            break;
+// The maps in chain order: maps[i] converts categories[i] to categories[i + 1]
+struct Mappings {
+    categories: Vec<String>,
+    maps: Vec<Map>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum AlmanacError {
+    MalformedHeader {
+        line: usize,
+        text: String,
+    },
+    // Two maps out of the same category, or two into the same one
+    Branch {
+        line: usize,
+        category: String,
+    },
+    // The maps don't chain together into one path from a first category to a last
+    NotAPath,
+    // The maps chain together, but aren't listed in chain order
+    OutOfOrder {
+        line: usize,
+        expected: String,
+        found: String,
+    },
+    UnknownCategory(String),
+    // Can only convert along the chain, not back up it
+    NoRoute {
+        from: String,
+        to: String,
+    },
+}
+
+impl fmt::Display for AlmanacError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MalformedHeader { line, text } => {
+                write!(f, "line {}: '{}' isn't a map header", line, text)
+            }
+            Self::Branch { line, category } => {
+                write!(f, "line {}: second map involving '{}'", line, category)
+            }
+            Self::NotAPath => write!(f, "the maps don't form a single chain"),
+            Self::OutOfOrder {
+                line,
+                expected,
+                found,
+            } => write!(
+                f,
+                "line {}: expected a map from '{}' but found one from '{}'",
+                line, expected, found
+            ),
+            Self::UnknownCategory(category) => write!(f, "no category '{}'", category),
+            Self::NoRoute { from, to } => write!(f, "can't convert from '{}' to '{}'", from, to),
         }
-        min_location += 1;
     }
-    min_location
 }
 
-struct Mappings(Vec<Map>);
-
 impl Mappings {
-    fn create<'a, I>(input_lines: I) -> Self
+    // first_line is the line number of the first line given, for reporting problems
+    fn create<'a, I>(input_lines: I, first_line: usize) -> Result<Self, AlmanacError>
     where
         I: Iterator<Item = &'a str>,
     {
-        let mut map_vec = Mappings(Vec::new());
+        let mut maps = Vec::new();
+        // (source, destination, line number) for each map
+        let mut headers: Vec<(String, String, usize)> = Vec::new();
         let mut current_map: Option<Map> = None;
 
-        for line in input_lines {
+        for (line_number, line) in (first_line..).zip(input_lines) {
             if let Some(mut map) = current_map {
                 if line.is_empty() {
-                    map_vec.0.push(map);
+                    maps.push(map);
                     current_map = None;
                 } else {
                     // Add a new entry to the current mapping
@@ -65,69 +124,395 @@ impl Mappings {
                         source_start: numbers_split.next().unwrap(),
                         length: numbers_split.next().unwrap(),
                     });
+                    map.lines.push(line_number);
                     current_map = Some(map);
                 }
-            } else {
-                // Start a new mapping
+            } else if !line.is_empty() {
+                // Start a new mapping; extra blank lines between maps are skipped
+                let (source, destination) = line
+                    .strip_suffix(" map:")
+                    .and_then(|categories| categories.split_once("-to-"))
+                    .ok_or_else(|| AlmanacError::MalformedHeader {
+                        line: line_number,
+                        text: line.to_string(),
+                    })?;
+                headers.push((source.to_string(), destination.to_string(), line_number));
                 current_map = Some(Map::new());
             }
         }
         // EOF - end current map
-        let map = current_map.unwrap();
-        map_vec.0.push(map);
-        map_vec
+        maps.extend(current_map);
+
+        let categories = Self::chain_categories(&headers)?;
+        Ok(Mappings { categories, maps })
     }
 
-    fn map(&self, source_value: i64) -> i64 {
-        let mut current = source_value;
-        for map in &self.0 {
-            current = map.map(current)
+    // Check the maps form a single path through the categories, listed in order, and
+    // return the categories along it
+    fn chain_categories(headers: &[(String, String, usize)]) -> Result<Vec<String>, AlmanacError> {
+        let mut next: HashMap<&str, &str> = HashMap::new();
+        let mut previous: HashMap<&str, &str> = HashMap::new();
+        for (source, destination, line) in headers {
+            if next.insert(source, destination).is_some() {
+                return Err(AlmanacError::Branch {
+                    line: *line,
+                    category: source.clone(),
+                });
+            }
+            if previous.insert(destination, source).is_some() {
+                return Err(AlmanacError::Branch {
+                    line: *line,
+                    category: destination.clone(),
+                });
+            }
+        }
+
+        let mut starts = next.keys().filter(|source| !previous.contains_key(*source));
+        let (Some(start), None) = (starts.next(), starts.next()) else {
+            return Err(AlmanacError::NotAPath);
+        };
+        let mut categories = vec![start.to_string()];
+        while let Some(destination) = next.get(categories.last().unwrap().as_str()) {
+            categories.push(destination.to_string());
+        }
+        if categories.len() != headers.len() + 1 {
+            // Some maps are off in a loop of their own
+            return Err(AlmanacError::NotAPath);
         }
-        current
+
+        for ((source, _, line), expected) in headers.iter().zip(&categories) {
+            if source != expected {
+                return Err(AlmanacError::OutOfOrder {
+                    line: *line,
+                    expected: expected.clone(),
+                    found: source.clone(),
+                });
+            }
+        }
+        Ok(categories)
     }
 
-    fn reverse_map(&self, dest_value: i64) -> i64 {
-        let mut current = dest_value;
-        for map in self.0.iter().rev() {
-            current = map.reverse_map(current)
+    fn position(&self, category: &str) -> Result<usize, AlmanacError> {
+        self.categories
+            .iter()
+            .position(|c| c == category)
+            .ok_or_else(|| AlmanacError::UnknownCategory(category.to_string()))
+    }
+
+    // The composed map from one category to a later one (or the same one)
+    fn route(&self, from: &str, to: &str) -> Result<Map, AlmanacError> {
+        let (start, end) = (self.position(from)?, self.position(to)?);
+        if end < start {
+            return Err(AlmanacError::NoRoute {
+                from: from.to_string(),
+                to: to.to_string(),
+            });
         }
-        current
+        Ok(self.maps[start..end]
+            .iter()
+            .fold(Map::new().normalize(), |chain, map| chain.compose(map)))
+    }
+
+    #[allow(dead_code)]
+    fn convert(&self, from: &str, to: &str, value: i64) -> Result<i64, AlmanacError> {
+        Ok(self.route(from, to)?.map(value))
+    }
+
+    #[allow(dead_code)]
+    fn convert_range(
+        &self,
+        from: &str,
+        to: &str,
+        range: Range<i64>,
+    ) -> Result<Vec<Range<i64>>, AlmanacError> {
+        Ok(self.route(from, to)?.map_range(range))
+    }
+
+    #[allow(dead_code)]
+    fn validate(&self) -> Vec<Overlap> {
+        self.maps.iter().flat_map(Map::validate).collect()
+    }
+
+    // Every seed that ends up at this location
+    #[allow(dead_code)]
+    fn inverse(&self, location: i64) -> Vec<i64> {
+        self.maps.iter().rev().fold(vec![location], |values, map| {
+            values
+                .into_iter()
+                .flat_map(|value| map.inverse(value))
+                .sorted()
+                .dedup()
+                .collect()
+        })
+    }
+
+    // A single map doing the work of the whole chain
+    fn composed(&self) -> Map {
+        self.route(&self.categories[0], self.categories.last().unwrap())
+            .unwrap()
     }
 }
 
-#[derive(Clone)]
+// Entries are tried in order, so where source ranges overlap the first one wins.
+// A normalized map has its entries sorted, non-overlapping and contiguous, with any gaps
+// filled by identity entries, and the first and last entries really moving something.
+#[derive(Clone, Debug)]
 struct Map {
     entries: Vec<MapEntry>,
+    normalized: bool,
+    // Input line number of each entry, if read from the almanac
+    lines: Vec<usize>,
 }
 
 impl Map {
     fn new() -> Self {
         Map {
             entries: Vec::new(),
+            normalized: false,
+            lines: Vec::new(),
         }
     }
 
     fn map(&self, input: i64) -> i64 {
-        let correct_map = self.entries.iter().find(|map| map.has_mapping_for(input));
+        let correct_map = if self.normalized {
+            let index = self
+                .entries
+                .partition_point(|entry| entry.source_start + entry.length <= input);
+            self.entries
+                .get(index)
+                .filter(|entry| entry.has_mapping_for(input))
+        } else {
+            self.entries.iter().find(|map| map.has_mapping_for(input))
+        };
         match correct_map {
             Some(map) => map.map(input),
             None => input,
         }
     }
 
-    fn reverse_map(&self, output: i64) -> i64 {
-        let correct_map = self
+    // Split the range at the boundaries of the entries it overlaps, shifting the parts
+    // covered by an entry and passing the gaps between them through unchanged
+    fn map_range(&self, range: Range<i64>) -> Vec<Range<i64>> {
+        self.pieces(range)
+            .into_iter()
+            .map(|(piece, shift)| (piece.start + shift)..(piece.end + shift))
+            .collect()
+    }
+
+    // The range cut into the longest pieces that each get the same shift
+    fn pieces(&self, range: Range<i64>) -> Vec<(Range<i64>, i64)> {
+        let mut boundaries: Vec<i64> = self
             .entries
             .iter()
-            .find(|map| map.has_reverse_mapping_for(output));
-        match correct_map {
-            Some(map) => map.reverse_map(output),
-            None => output,
+            .flat_map(|entry| [entry.source_start, entry.source_start + entry.length])
+            .filter(|boundary| range.contains(boundary))
+            .chain([range.start, range.end])
+            .collect();
+        boundaries.sort_unstable();
+        boundaries.dedup();
+
+        let mut pieces: Vec<(Range<i64>, i64)> = Vec::new();
+        for window in boundaries.windows(2) {
+            let shift = self.map(window[0]) - window[0];
+            match pieces.last_mut() {
+                Some((last, last_shift)) if *last_shift == shift => last.end = window[1],
+                _ => pieces.push((window[0]..window[1], shift)),
+            }
+        }
+        pieces
+    }
+
+    // Every input sent to this output: none if it's covered by an entry's source range
+    // but no destination range, several if destination ranges overlap
+    #[allow(dead_code)]
+    fn inverse(&self, output: i64) -> Vec<i64> {
+        self.pieces(i64::MIN..i64::MAX)
+            .into_iter()
+            .map(|(piece, shift)| (piece, output - shift))
+            .filter(|(piece, input)| piece.contains(input))
+            .map(|(_, input)| input)
+            .sorted()
+            .collect()
+    }
+
+    // Every input sent into this output range, as sorted, non-overlapping ranges
+    #[allow(dead_code)]
+    fn inverse_range(&self, outputs: Range<i64>) -> Vec<Range<i64>> {
+        let mut inputs: Vec<Range<i64>> = self
+            .pieces(i64::MIN..i64::MAX)
+            .into_iter()
+            .filter_map(|(piece, shift)| {
+                let start = i64::max(piece.start, outputs.start - shift);
+                let end = i64::min(piece.end, outputs.end - shift);
+                (start < end).then_some(start..end)
+            })
+            .collect();
+        inputs.sort_by_key(|range| range.start);
+        // Pieces don't overlap, but neighbouring ones can join up
+        let mut merged: Vec<Range<i64>> = Vec::new();
+        for range in inputs {
+            match merged.last_mut() {
+                Some(last) if last.end == range.start => last.end = range.end,
+                _ => merged.push(range),
+            }
+        }
+        merged
+    }
+
+    // Entries whose source ranges overlap (so the later one is partly ignored) or
+    // whose destination ranges overlap (so some outputs have several inputs)
+    fn validate(&self) -> Vec<Overlap> {
+        let mut overlaps = Vec::new();
+        for ((i, a), (j, b)) in self.entries.iter().enumerate().tuple_combinations() {
+            let lines = (
+                self.lines.get(i).copied().unwrap_or(i + 1),
+                self.lines.get(j).copied().unwrap_or(j + 1),
+            );
+            let sides = [
+                (OverlapKind::Source, a.source_start, b.source_start),
+                (OverlapKind::Destination, a.dest_start, b.dest_start),
+            ];
+            for (kind, a_start, b_start) in sides {
+                let start = i64::max(a_start, b_start);
+                let end = i64::min(a_start + a.length, b_start + b.length);
+                if start < end {
+                    overlaps.push(Overlap {
+                        kind,
+                        lines,
+                        range: start..end,
+                    });
+                }
+            }
         }
+        overlaps
+    }
+
+    // Equivalent to applying this map and then the other one
+    fn compose(&self, other: &Map) -> Map {
+        let pieces = self
+            .pieces(i64::MIN..i64::MAX)
+            .into_iter()
+            .flat_map(|(piece, shift)| {
+                other
+                    .pieces((piece.start + shift)..(piece.end + shift))
+                    .into_iter()
+                    .map(move |(other_piece, other_shift)| {
+                        (
+                            (other_piece.start - shift)..(other_piece.end - shift),
+                            shift + other_shift,
+                        )
+                    })
+            })
+            .collect();
+        Map::from_pieces(pieces)
+    }
+
+    fn normalize(&self) -> Map {
+        if self.normalized {
+            return self.clone();
+        }
+        Map::from_pieces(self.pieces(i64::MIN..i64::MAX))
+    }
+
+    // Pieces must be sorted and non-overlapping
+    fn from_pieces(pieces: Vec<(Range<i64>, i64)>) -> Map {
+        let mut entries: Vec<MapEntry> = Vec::new();
+        // Identity pieces aren't stored directly: at either end they're implied, and
+        // in between they're filled in as gaps
+        for (piece, shift) in pieces {
+            if piece.is_empty() || shift == 0 {
+                continue;
+            }
+            if let Some(last) = entries.last() {
+                let gap_start = last.source_end();
+                if last.shift() == shift && gap_start == piece.start {
+                    entries.last_mut().unwrap().length += piece.end - piece.start;
+                    continue;
+                }
+                if gap_start < piece.start {
+                    entries.push(MapEntry {
+                        source_start: gap_start,
+                        dest_start: gap_start,
+                        length: piece.start - gap_start,
+                    });
+                }
+            }
+            entries.push(MapEntry {
+                source_start: piece.start,
+                dest_start: piece.start + shift,
+                length: piece.end - piece.start,
+            });
+        }
+
+        Map {
+            entries,
+            normalized: true,
+            lines: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum OverlapKind {
+    Source,
+    Destination,
+}
+
+// Two entries of a map covering some of the same values.  Lines count from 1, or
+// number the entries if the map wasn't read from input.
+#[derive(Debug, PartialEq, Eq)]
+struct Overlap {
+    kind: OverlapKind,
+    lines: (usize, usize),
+    range: Range<i64>,
+}
+
+impl fmt::Display for Overlap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            OverlapKind::Source => "source",
+            OverlapKind::Destination => "destination",
+        };
+        write!(
+            f,
+            "lines {} and {}: {} ranges overlap on {}..{}",
+            self.lines.0, self.lines.1, kind, self.range.start, self.range.end
+        )
     }
 }
 
-#[derive(Clone)]
+// Maps are equal when they send every value to the same place
+impl PartialEq for Map {
+    fn eq(&self, other: &Self) -> bool {
+        self.normalize().entries == other.normalize().entries
+    }
+}
+
+impl fmt::Display for Map {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let map = self.normalize();
+        if map.entries.is_empty() {
+            return writeln!(f, "everything unchanged");
+        }
+        for entry in &map.entries {
+            write!(f, "{}..{} ", entry.source_start, entry.source_end())?;
+            if entry.shift() == 0 {
+                writeln!(f, "unchanged")?;
+            } else {
+                writeln!(
+                    f,
+                    "-> {}..{} ({:+})",
+                    entry.dest_start,
+                    entry.dest_start + entry.length,
+                    entry.shift()
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 struct MapEntry {
     source_start: i64,
     dest_start: i64,
@@ -147,23 +532,12 @@ impl MapEntry {
         }
     }
 
-    fn shift(&self) -> i64 {
-        self.dest_start - self.source_start
-    }
-
-    fn has_reverse_mapping_for(&self, dest: i64) -> bool {
-        dest >= self.dest_start && dest < self.dest_start + self.length
+    fn source_end(&self) -> i64 {
+        self.source_start + self.length
     }
 
-    fn reverse_map(&self, output: i64) -> i64 {
-        // There's a bug here - don't think every output will have
-        // a corresponding input, so should return an Option.
-        // Luckily didn't seem to matter for my puzzle input
-        if self.has_reverse_mapping_for(output) {
-            output - self.shift()
-        } else {
-            output
-        }
+    fn shift(&self) -> i64 {
+        self.dest_start - self.source_start
     }
 }
 
@@ -171,6 +545,80 @@ impl MapEntry {
 mod tests {
     use super::*;
 
+    const EXAMPLE: &str = "seeds: 79 14 55 13
+
+seed-to-soil map:
+50 98 2
+52 50 48
+
+soil-to-fertilizer map:
+0 15 37
+37 52 2
+39 0 15
+
+fertilizer-to-water map:
+49 53 8
+0 11 42
+42 0 7
+57 7 4
+
+water-to-light map:
+88 18 7
+18 25 70
+
+light-to-temperature map:
+45 77 23
+81 45 19
+68 64 13
+
+temperature-to-humidity map:
+0 69 1
+1 0 69
+
+humidity-to-location map:
+60 56 37
+56 93 4";
+
+    // The original part 2: count up through locations until one maps back to a seed
+    fn part2_brute_force(seed_numbers: &[i64], mappings: &Mappings) -> i64 {
+        let ranges = seed_ranges(seed_numbers);
+        let mut min_location = 0;
+        loop {
+            let inputs = mappings.inverse(min_location);
+            if inputs
+                .iter()
+                .any(|input| ranges.iter().any(|range| range.contains(input)))
+            {
+                break;
+            }
+            min_location += 1;
+        }
+        min_location
+    }
+
+    // Step-by-step evaluation, to check the composed chain against
+    impl Mappings {
+        fn map(&self, source_value: i64) -> i64 {
+            let mut current = source_value;
+            for map in &self.maps {
+                current = map.map(current)
+            }
+            current
+        }
+
+        // Push whole ranges through every map at once.  Each map can split a range into
+        // several, so the result is a (possibly overlapping) set of ranges covering every
+        // possible output.
+        fn map_ranges(&self, ranges: Vec<Range<i64>>) -> Vec<Range<i64>> {
+            self.maps.iter().fold(ranges, |ranges, map| {
+                ranges
+                    .into_iter()
+                    .flat_map(|range| map.map_range(range))
+                    .collect()
+            })
+        }
+    }
+
     #[test]
     fn check_seeds_parsing() {
         let input = "seeds: 3429320627 235304036";
@@ -200,4 +648,313 @@ mod tests {
         assert_eq!(map.map(51), 53);
         assert_eq!(map.map(99), 51);
     }
+
+    #[test]
+    fn check_map_range() {
+        let mut map = Map::new();
+        map.entries.push(MapEntry {
+            dest_start: 50,
+            source_start: 98,
+            length: 2,
+        });
+        map.entries.push(MapEntry {
+            dest_start: 52,
+            source_start: 50,
+            length: 48,
+        });
+        assert_eq!(map.map_range(0..10), vec![0..10]);
+        assert_eq!(map.map_range(45..55), vec![45..50, 52..57]);
+        assert_eq!(map.map_range(90..105), vec![92..100, 50..52, 100..105]);
+    }
+
+    #[test]
+    fn check_day05_example() {
+        assert_eq!(day05(EXAMPLE), ("35".to_string(), "46".to_string()));
+    }
+
+    #[test]
+    fn check_day05_part2_against_brute_force() {
+        let mut lines = EXAMPLE.lines();
+        lines.next();
+        lines.next();
+        let mappings = Mappings::create(lines, 3).unwrap();
+        let seed_sets = [
+            vec![79, 14, 55, 13],
+            vec![0, 1],
+            vec![97, 5],
+            vec![10, 30, 60, 2, 90, 3],
+        ];
+        for seeds in seed_sets {
+            assert_eq!(
+                part2(&seeds, &mappings),
+                part2_brute_force(&seeds, &mappings)
+            );
+        }
+    }
+
+    fn example_mappings() -> Mappings {
+        Mappings::create(EXAMPLE.lines().skip(2), 3).unwrap()
+    }
+
+    #[test]
+    fn check_composed_chain() {
+        let mappings = example_mappings();
+        let chain = mappings.composed();
+        for value in -10..200 {
+            assert_eq!(chain.map(value), mappings.map(value));
+        }
+        let ranges = seed_ranges(&[0, 100, 79, 14]);
+        let mut composed: Vec<Range<i64>> = ranges
+            .iter()
+            .flat_map(|range| chain.map_range(range.clone()))
+            .collect();
+        let mut step_by_step = mappings.map_ranges(ranges);
+        for ranges in [&mut composed, &mut step_by_step] {
+            let values: Vec<i64> = ranges
+                .iter()
+                .flat_map(|range| range.clone())
+                .sorted()
+                .collect();
+            *ranges = values.into_iter().map(|value| value..value + 1).collect();
+        }
+        assert_eq!(composed, step_by_step);
+    }
+
+    #[test]
+    fn check_normalized_map() {
+        let mut map = Map::new();
+        for (dest_start, source_start, length) in [(52, 50, 48), (50, 98, 2), (20, 10, 5)] {
+            map.entries.push(MapEntry {
+                dest_start,
+                source_start,
+                length,
+            });
+        }
+        let normalized = map.normalize();
+        assert_eq!(
+            normalized.entries,
+            vec![
+                MapEntry {
+                    source_start: 10,
+                    dest_start: 20,
+                    length: 5
+                },
+                MapEntry {
+                    source_start: 15,
+                    dest_start: 15,
+                    length: 35
+                },
+                MapEntry {
+                    source_start: 50,
+                    dest_start: 52,
+                    length: 48
+                },
+                MapEntry {
+                    source_start: 98,
+                    dest_start: 50,
+                    length: 2
+                },
+            ]
+        );
+        assert_eq!(
+            normalized.to_string(),
+            "10..15 -> 20..25 (+10)\n15..50 unchanged\n50..98 -> 52..100 (+2)\n98..100 -> 50..52 (-48)\n"
+        );
+        for value in 0..110 {
+            assert_eq!(normalized.map(value), map.map(value));
+        }
+    }
+
+    #[test]
+    fn check_map_equality() {
+        let entry = |dest_start, source_start, length| MapEntry {
+            dest_start,
+            source_start,
+            length,
+        };
+        let whole = Map {
+            entries: vec![entry(20, 10, 10)],
+            ..Map::new()
+        };
+        // Split in two, out of order, with a do-nothing entry thrown in
+        let split = Map {
+            entries: vec![entry(25, 15, 5), entry(40, 40, 10), entry(20, 10, 5)],
+            ..Map::new()
+        };
+        assert_eq!(whole, split);
+
+        // Earlier entries take priority where they overlap
+        let overlapping = Map {
+            entries: vec![entry(20, 10, 10), entry(100, 5, 30)],
+            ..Map::new()
+        };
+        assert_eq!(overlapping.map(12), 22);
+        assert_eq!(overlapping.map(25), 120);
+        assert_ne!(whole, overlapping);
+
+        // 10..20 goes up and comes back down, while 20..30 only comes down
+        let back = Map {
+            entries: vec![entry(10, 20, 10)],
+            ..Map::new()
+        };
+        assert_eq!(whole.compose(&back), back);
+
+        // Swapping two ranges twice does nothing
+        let swap = Map {
+            entries: vec![entry(20, 10, 10), entry(10, 20, 10)],
+            ..Map::new()
+        };
+        let identity = swap.compose(&swap);
+        assert_eq!(identity, Map::new());
+        assert_eq!(identity.to_string(), "everything unchanged\n");
+    }
+
+    #[test]
+    fn check_inverse() {
+        let entry = |dest_start, source_start, length| MapEntry {
+            dest_start,
+            source_start,
+            length,
+        };
+        // 10..20 moves up to 20..30, leaving nothing to land on 10..20, while 30..40
+        // lands on top of 20..30 as well, and 20..30 itself stays put
+        let map = Map {
+            entries: vec![entry(20, 10, 10), entry(20, 30, 10)],
+            ..Map::new()
+        };
+        assert_eq!(map.inverse(5), vec![5]);
+        assert_eq!(map.inverse(15), vec![]);
+        assert_eq!(map.inverse(25), vec![15, 25, 35]);
+        assert_eq!(map.inverse(35), vec![]);
+        assert_eq!(map.inverse_range(30..40), vec![]);
+        assert_eq!(map.inverse(45), vec![45]);
+        assert_eq!(map.inverse_range(0..50), vec![0..50]);
+        assert_eq!(map.inverse_range(12..22), vec![10..12, 20..22, 30..32]);
+        for output in 0..50 {
+            let brute_force: Vec<i64> = (0..50).filter(|input| map.map(*input) == output).collect();
+            assert_eq!(map.inverse(output), brute_force);
+        }
+    }
+
+    #[test]
+    fn check_chain_inverse() {
+        let mappings = example_mappings();
+        for location in 0..110 {
+            let brute_force: Vec<i64> = (0..110)
+                .filter(|seed| mappings.map(*seed) == location)
+                .collect();
+            assert_eq!(mappings.inverse(location), brute_force);
+        }
+    }
+
+    #[test]
+    fn check_validate() {
+        assert_eq!(example_mappings().validate(), vec![]);
+
+        let input =
+            "seed-to-soil map:\n50 98 2\n52 50 48\n60 95 10\n\nsoil-to-fertilizer map:\n0 15 37";
+        let mappings = Mappings::create(input.lines(), 3).unwrap();
+        let overlaps = mappings.validate();
+        assert_eq!(
+            overlaps,
+            vec![
+                Overlap {
+                    kind: OverlapKind::Source,
+                    lines: (4, 6),
+                    range: 98..100
+                },
+                Overlap {
+                    kind: OverlapKind::Source,
+                    lines: (5, 6),
+                    range: 95..98
+                },
+                Overlap {
+                    kind: OverlapKind::Destination,
+                    lines: (5, 6),
+                    range: 60..70
+                },
+            ]
+        );
+        assert_eq!(
+            overlaps[0].to_string(),
+            "lines 4 and 6: source ranges overlap on 98..100"
+        );
+    }
+
+    #[test]
+    fn check_convert() {
+        let mappings = example_mappings();
+        assert_eq!(mappings.categories.len(), 8);
+        assert_eq!(mappings.convert("seed", "location", 79), Ok(82));
+        assert_eq!(mappings.convert("seed", "light", 79), Ok(74));
+        assert_eq!(mappings.convert("soil", "humidity", 81), Ok(78));
+        assert_eq!(mappings.convert("water", "water", 5), Ok(5));
+        assert_eq!(
+            mappings.convert_range("seed", "soil", 45..55),
+            Ok(vec![45..50, 52..57])
+        );
+        assert_eq!(
+            mappings.convert("location", "seed", 82),
+            Err(AlmanacError::NoRoute {
+                from: "location".to_string(),
+                to: "seed".to_string()
+            })
+        );
+        assert_eq!(
+            mappings.convert("seed", "weather", 1),
+            Err(AlmanacError::UnknownCategory("weather".to_string()))
+        );
+    }
+
+    #[test]
+    fn check_extra_blank_lines() {
+        let mappings = Mappings::create(
+            "\nseed-to-soil map:\n1 2 3\n\n\nsoil-to-water map:\n4 5 6\n\n".lines(),
+            1,
+        )
+        .unwrap();
+        assert_eq!(mappings.maps.len(), 2);
+        assert_eq!(mappings.convert("seed", "water", 2), Ok(1));
+        assert_eq!(mappings.convert("seed", "water", 6), Ok(5));
+    }
+
+    #[test]
+    fn check_almanac_errors() {
+        let create = |input: &str| Mappings::create(input.lines(), 1).err();
+        assert_eq!(
+            create("seed-to-soil map:\n1 2 3\n\nsoil to water map:\n4 5 6"),
+            Some(AlmanacError::MalformedHeader {
+                line: 4,
+                text: "soil to water map:".to_string()
+            })
+        );
+        assert_eq!(
+            create("seed-to-soil map:\n1 2 3\n\nseed-to-water map:\n4 5 6"),
+            Some(AlmanacError::Branch {
+                line: 4,
+                category: "seed".to_string()
+            })
+        );
+        assert_eq!(
+            create("a-to-b map:\n1 2 3\n\nc-to-d map:\n\nd-to-c map:\n"),
+            Some(AlmanacError::NotAPath)
+        );
+        assert_eq!(
+            create("soil-to-water map:\n1 2 3\n\nseed-to-soil map:\n4 5 6"),
+            Some(AlmanacError::OutOfOrder {
+                line: 1,
+                expected: "seed".to_string(),
+                found: "soil".to_string()
+            })
+        );
+        assert_eq!(
+            AlmanacError::OutOfOrder {
+                line: 1,
+                expected: "seed".to_string(),
+                found: "soil".to_string()
+            }
+            .to_string(),
+            "line 1: expected a map from 'seed' but found one from 'soil'"
+        );
+    }
 }
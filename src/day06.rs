@@ -1,8 +1,9 @@
-use bigdecimal::{BigDecimal, FromPrimitive, ToPrimitive};
+use num::{BigUint, CheckedSub};
+use std::ops::RangeInclusive;
 
 pub fn day06(input_lines: &str) -> (String, String) {
     let part1_races = read_races_part1(input_lines);
-    let answer1: u64 = part1_races
+    let answer1: u128 = part1_races
         .iter()
         .map(Race::count_record_breakers)
         .product();
@@ -19,13 +20,13 @@ fn read_races_part1(input_lines: &str) -> Vec<Race> {
         .unwrap()
         .split_ascii_whitespace()
         .skip(1)
-        .map(|t| t.parse::<u64>().unwrap());
+        .map(|t| t.parse::<u128>().unwrap());
     let distances = lines
         .next()
         .unwrap()
         .split_ascii_whitespace()
         .skip(1)
-        .map(|t| t.parse::<u64>().unwrap());
+        .map(|t| t.parse::<u128>().unwrap());
 
     times
         .zip(distances)
@@ -46,41 +47,192 @@ fn read_race_part2(input_lines: &str) -> Race {
     }
 }
 
-fn parse_spaced_out_input_numbers(input_line: &str) -> u64 {
+fn parse_spaced_out_input_numbers(input_line: &str) -> u128 {
     input_line
         .split(':')
         .nth(1)
         .unwrap()
         .split_ascii_whitespace()
         .fold(String::new(), |acc, s| acc + s.trim())
-        .parse::<u64>()
+        .parse::<u128>()
         .unwrap()
 }
 
 struct Race {
-    record_mm: u64,
-    time_ms: u64,
+    record_mm: u128,
+    time_ms: u128,
 }
 
 impl Race {
-    fn count_record_breakers(&self) -> u64 {
-        // Solve quadratic inequality:
-        // x^2 - rx + r < 0
-        // x = button time
-        // r = race time
-        // d = record distance
-        let r = BigDecimal::from_u64(self.time_ms).unwrap();
-        let d = BigDecimal::from_u64(self.record_mm).unwrap();
-        let discriminant: BigDecimal = &r * &r - 4 * &d;
-        let upper_limit: f64 = (&r + discriminant.sqrt().unwrap()).to_f64().unwrap() / 2.0;
-        let mut lower_limit: f64 = (&r - discriminant.sqrt().unwrap()).to_f64().unwrap() / 2.0;
-        if lower_limit < 0.0 {
-            lower_limit = 0.0;
+    fn count_record_breakers(&self) -> u128 {
+        self.count_record_breakers_with(&Linear)
+    }
+
+    fn count_record_breakers_with(&self, model: &dyn BoatModel) -> u128 {
+        model
+            .winning_holds(self)
+            .map_or(0, |holds| holds.end() - holds.start() + 1)
+    }
+
+    // Holding the button for h ms goes h * (r - h) mm, which beats the record d when
+    // h^2 - rh + d < 0, i.e. strictly between the roots (r ± sqrt(r^2 - 4d)) / 2.
+    // The integer square root gets within a step or two of the first and last winning
+    // hold times, and checking the distance directly pins them down exactly.
+    fn winning_holds(&self) -> Option<RangeInclusive<u128>> {
+        let r = self.time_ms;
+        let best = r / 2;
+        if !self.beats_record(best) {
+            return None;
+        }
+
+        let r_big = BigUint::from(r);
+        let discriminant = (&r_big * &r_big)
+            .checked_sub(&(BigUint::from(self.record_mm) * 4_u32))
+            .unwrap_or_default();
+        let root = discriminant.sqrt();
+        let estimate = |numerator: BigUint| -> u128 {
+            u128::try_from(numerator / 2_u32)
+                .unwrap_or(u128::MAX)
+                .clamp(0, r)
+        };
+
+        let mut first = u128::min(estimate(&r_big - &root), best);
+        while !self.beats_record(first) {
+            first += 1;
+        }
+        while first > 0 && self.beats_record(first - 1) {
+            first -= 1;
+        }
+
+        let mut last = u128::max(estimate(&r_big + &root), best);
+        while !self.beats_record(last) {
+            last -= 1;
         }
-        (upper_limit.floor() - lower_limit.floor()) as u64
+        while last < r && self.beats_record(last + 1) {
+            last += 1;
+        }
+        Some(first..=last)
+    }
+
+    fn beats_record(&self, hold_ms: u128) -> bool {
+        // A distance too big for a u128 certainly beats the record
+        hold_ms
+            .checked_mul(self.time_ms - hold_ms)
+            .is_none_or(|distance| distance > self.record_mm)
     }
 }
 
+// How far a boat goes in a race of time_ms if the button is held for hold_ms, or None
+// if that's too far to fit in a u128 (and so certainly beats any record).
+//
+// The generic solver needs the distances to rise to a peak and then fall away, though
+// they may sit flat at zero to begin with.
+trait BoatModel {
+    fn distance(&self, hold_ms: u128, time_ms: u128) -> Option<u128>;
+
+    fn winning_holds(&self, race: &Race) -> Option<RangeInclusive<u128>> {
+        search_winning_holds(self, race)
+    }
+}
+
+// Speed is the hold time
+struct Linear;
+
+impl BoatModel for Linear {
+    fn distance(&self, hold_ms: u128, time_ms: u128) -> Option<u128> {
+        hold_ms.checked_mul(time_ms - hold_ms)
+    }
+
+    // Solving the quadratic beats searching
+    fn winning_holds(&self, race: &Race) -> Option<RangeInclusive<u128>> {
+        race.winning_holds()
+    }
+}
+
+// Speed is the square of the hold time
+#[allow(dead_code)]
+struct Quadratic;
+
+impl BoatModel for Quadratic {
+    fn distance(&self, hold_ms: u128, time_ms: u128) -> Option<u128> {
+        hold_ms.checked_mul(hold_ms)?.checked_mul(time_ms - hold_ms)
+    }
+}
+
+// Speed is the hold time, up to a maximum
+#[allow(dead_code)]
+struct Capped {
+    max_speed: u128,
+}
+
+impl BoatModel for Capped {
+    fn distance(&self, hold_ms: u128, time_ms: u128) -> Option<u128> {
+        u128::min(hold_ms, self.max_speed).checked_mul(time_ms - hold_ms)
+    }
+}
+
+// Speed only starts building once the button has been held for delay_ms
+#[allow(dead_code)]
+struct Delayed {
+    delay_ms: u128,
+}
+
+impl BoatModel for Delayed {
+    fn distance(&self, hold_ms: u128, time_ms: u128) -> Option<u128> {
+        hold_ms
+            .saturating_sub(self.delay_ms)
+            .checked_mul(time_ms - hold_ms)
+    }
+}
+
+// Binary search for the peak distance, then out either side of it for the first and
+// last hold times beating the record
+fn search_winning_holds<M: BoatModel + ?Sized>(
+    model: &M,
+    race: &Race,
+) -> Option<RangeInclusive<u128>> {
+    let time_ms = race.time_ms;
+    let distance = |hold_ms| model.distance(hold_ms, time_ms);
+    let beats_record = |hold_ms| distance(hold_ms).is_none_or(|d| d > race.record_mm);
+    // Too far to fit counts as further than anything that does
+    let further = |a: Option<u128>, b: Option<u128>| match (a, b) {
+        (None, _) => false,
+        (Some(_), None) => true,
+        (Some(a), Some(b)) => a < b,
+    };
+
+    let rising = |hold_ms: u128| {
+        let (here, next) = (distance(hold_ms), distance(hold_ms + 1));
+        further(here, next) || (here == Some(0) && next == Some(0))
+    };
+    let peak = partition_point(0, time_ms, rising);
+    if !beats_record(peak) {
+        return None;
+    }
+    let first = partition_point(0, peak, |hold_ms| !beats_record(hold_ms));
+    let last = if beats_record(time_ms) {
+        time_ms
+    } else {
+        partition_point(peak, time_ms, beats_record) - 1
+    };
+    Some(first..=last)
+}
+
+// The first value in low..=high for which the predicate is false, where it's true for
+// everything before that point and false for everything after.  The predicate is never
+// asked about high itself, which is returned if nothing before it is false.
+fn partition_point<F: Fn(u128) -> bool>(mut low: u128, mut high: u128, predicate: F) -> u128 {
+    while low < high {
+        let middle = low + (high - low) / 2;
+        if predicate(middle) {
+            low = middle + 1;
+        } else {
+            high = middle;
+        }
+    }
+    low
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -102,4 +254,147 @@ mod tests {
         };
         assert_eq!(race.count_record_breakers(), 71503);
     }
+
+    #[test]
+    fn check_winning_holds() {
+        let race = Race {
+            record_mm: 200,
+            time_ms: 30,
+        };
+        // Holding for exactly 10 or 20 ms only equals the record
+        assert_eq!(race.winning_holds(), Some(11..=19));
+        let race = Race {
+            record_mm: 0,
+            time_ms: 5,
+        };
+        assert_eq!(race.winning_holds(), Some(1..=4));
+        let race = Race {
+            record_mm: 10,
+            time_ms: 6,
+        };
+        assert_eq!(race.winning_holds(), None);
+        assert_eq!(race.count_record_breakers(), 0);
+    }
+
+    #[test]
+    fn check_against_brute_force() {
+        for time_ms in 0..60 {
+            for record_mm in 0..=(time_ms * time_ms / 4 + 2) {
+                let race = Race { record_mm, time_ms };
+                let winners: Vec<u128> = (0..=time_ms)
+                    .filter(|hold| hold * (time_ms - hold) > record_mm)
+                    .collect();
+                let expected = winners
+                    .first()
+                    .map(|first| *first..=*winners.last().unwrap());
+                assert_eq!(race.winning_holds(), expected, "{} {}", time_ms, record_mm);
+            }
+        }
+    }
+
+    #[test]
+    fn check_huge_races() {
+        // Just short of the best possible distance, so only the middle hold time wins
+        let race = Race {
+            record_mm: (1 << 126) - 1,
+            time_ms: 1 << 64,
+        };
+        assert_eq!(race.winning_holds(), Some(1 << 63..=1 << 63));
+
+        // Precisely on the boundary of a perfect square discriminant
+        let time_ms = 1 << 62;
+        let hold = 1 << 20;
+        let race = Race {
+            record_mm: hold * (time_ms - hold),
+            time_ms,
+        };
+        assert_eq!(race.winning_holds(), Some(hold + 1..=time_ms - hold - 1));
+
+        // Distances bigger than any u128, checked exactly
+        let distance = |hold: u128| BigUint::from(hold) * BigUint::from(u128::MAX - hold);
+        for record_mm in [u128::MAX, u128::MAX / 3, 12345] {
+            let race = Race {
+                record_mm,
+                time_ms: u128::MAX,
+            };
+            let holds = race.winning_holds().unwrap();
+            let record = BigUint::from(record_mm);
+            assert!(distance(*holds.start()) > record);
+            assert!(distance(*holds.start() - 1) <= record);
+            assert!(distance(*holds.end()) > record);
+            assert!(distance(*holds.end() + 1) <= record);
+        }
+    }
+
+    fn brute_force(model: &dyn BoatModel, race: &Race) -> Option<RangeInclusive<u128>> {
+        let winners: Vec<u128> = (0..=race.time_ms)
+            .filter(|hold| {
+                model
+                    .distance(*hold, race.time_ms)
+                    .is_none_or(|d| d > race.record_mm)
+            })
+            .collect();
+        winners
+            .first()
+            .map(|first| *first..=*winners.last().unwrap())
+    }
+
+    #[test]
+    fn check_models_against_brute_force() {
+        let models: Vec<Box<dyn BoatModel>> = vec![
+            Box::new(Linear),
+            Box::new(Quadratic),
+            Box::new(Capped { max_speed: 4 }),
+            Box::new(Capped { max_speed: 100 }),
+            Box::new(Delayed { delay_ms: 3 }),
+            Box::new(Delayed { delay_ms: 50 }),
+        ];
+        for model in &models {
+            for time_ms in 0..40 {
+                for record_mm in (0..300).step_by(7) {
+                    let race = Race { record_mm, time_ms };
+                    assert_eq!(
+                        model.winning_holds(&race),
+                        brute_force(model.as_ref(), &race),
+                        "{} {}",
+                        time_ms,
+                        record_mm
+                    );
+                    // The generic search agrees with any closed form
+                    assert_eq!(
+                        search_winning_holds(model.as_ref(), &race),
+                        model.winning_holds(&race)
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn check_models() {
+        let race = Race {
+            record_mm: 9,
+            time_ms: 7,
+        };
+        assert_eq!(race.count_record_breakers_with(&Linear), 4);
+        // Distances 0, 6, 20, 36, 48, 50, 36, 0
+        assert_eq!(Quadratic.winning_holds(&race), Some(2..=6));
+        // Distances 0, 6, 10, 12, 12, 10, 6, 0 at full speed, with the cap at 2 giving
+        // 0, 6, 10, 8, 6, 4, 2, 0
+        assert_eq!(Capped { max_speed: 2 }.winning_holds(&race), Some(2..=2));
+        // Distances 0, 0, 0, 4, 6, 6, 4, 0
+        assert_eq!(Delayed { delay_ms: 2 }.winning_holds(&race), None);
+
+        // Too far to fit in a u128 at the peak
+        let race = Race {
+            record_mm: u128::MAX,
+            time_ms: 1 << 50,
+        };
+        let holds = Quadratic.winning_holds(&race).unwrap();
+        assert!(Quadratic.distance(*holds.start(), race.time_ms).is_none());
+        assert!(Quadratic
+            .distance(*holds.start() - 1, race.time_ms)
+            .is_some());
+        assert!(Quadratic.distance(*holds.end() + 1, race.time_ms).is_some());
+    }
 }
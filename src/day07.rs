@@ -1,7 +1,8 @@
-use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::cmp::{Ordering, Reverse};
+use std::fmt;
 
 use itertools::Itertools;
+use once_cell::sync::Lazy;
 
 pub fn day07(input_lines: &str) -> (String, String) {
     let answer1 = part1(input_lines);
@@ -10,194 +11,478 @@ pub fn day07(input_lines: &str) -> (String, String) {
 }
 
 fn part1(input_lines: &str) -> i32 {
-    let hands_bids: HashMap<CamelHand, i32> = input_lines
-        .lines()
-        .map(|l| {
-            let mut i = l.split_ascii_whitespace();
-            (
-                CamelHand::create(i.next().unwrap()),
-                i.next().unwrap().parse().unwrap(),
-            )
-        })
-        .collect();
-
-    winnings(&hands_bids)
+    let hands_bids = read_hands(input_lines, CamelHand::create);
+    winnings(&hands_bids, TiePolicy::InputOrder)
 }
 
 fn part2(input_lines: &str) -> i32 {
-    let hands_bids: HashMap<CamelHand, i32> = input_lines
+    let hands_bids = read_hands(input_lines, CamelHand::create_with_jokers);
+    winnings(&hands_bids, TiePolicy::InputOrder)
+}
+
+struct HandBid {
+    hand: CamelHand,
+    bid: i32,
+}
+
+fn read_hands(input_lines: &str, create_hand: fn(&str) -> CamelHand) -> Vec<HandBid> {
+    input_lines
         .lines()
         .map(|l| {
             let mut i = l.split_ascii_whitespace();
-            (
-                CamelHand::create_with_jokers(i.next().unwrap()),
-                i.next().unwrap().parse().unwrap(),
-            )
+            HandBid {
+                hand: create_hand(i.next().unwrap()),
+                bid: i.next().unwrap().parse().unwrap(),
+            }
         })
-        .collect();
+        .collect()
+}
 
-    winnings(&hands_bids)
+// How to rank hands that compare equal
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug)]
+enum TiePolicy {
+    // Equal hands all get the lowest of their ranks, and the next hand up skips past
+    // them, e.g. 1, 2, 2, 4
+    SharedRank,
+    // Equal hands keep their input order, the first getting the lowest rank
+    InputOrder,
 }
 
-fn winnings(hands_bids: &HashMap<CamelHand, i32>) -> i32 {
-    let mut sorted_hands = hands_bids.keys().collect_vec();
-    sorted_hands.sort_unstable_by(|h1, h2| h1.partial_cmp(h2).unwrap());
+// Hands from weakest to strongest, with their ranks (counting from 1)
+fn ranked(hands_bids: &[HandBid], policy: TiePolicy) -> Vec<(i32, &HandBid)> {
+    let mut sorted_hands = hands_bids.iter().collect_vec();
+    // Stable, so equal hands stay in input order
+    sorted_hands.sort_by(|h1, h2| h1.hand.cmp(&h2.hand));
+
+    let mut ranks: Vec<(i32, &HandBid)> = Vec::with_capacity(sorted_hands.len());
+    for (i, hand_bid) in sorted_hands.into_iter().enumerate() {
+        let mut rank: i32 = (i + 1).try_into().unwrap();
+        if let (TiePolicy::SharedRank, Some((previous_rank, previous))) = (policy, ranks.last()) {
+            if previous.hand == hand_bid.hand {
+                rank = *previous_rank;
+            }
+        }
+        ranks.push((rank, hand_bid));
+    }
+    ranks
+}
 
-    sorted_hands
+fn winnings(hands_bids: &[HandBid], policy: TiePolicy) -> i32 {
+    ranked(hands_bids, policy)
         .iter()
-        .enumerate()
-        .map(|(i, hand)| {
-            let rank: i32 = (i + 1).try_into().unwrap();
-            rank * hands_bids.get(hand).unwrap()
-        })
+        .map(|(rank, hand_bid)| rank * hand_bid.bid)
         .sum()
 }
 
-#[derive(PartialEq, Eq, Hash, Debug)]
-struct CamelHand(Vec<CamelCard>);
+#[allow(dead_code)]
+fn rank_table_csv(hands_bids: &[HandBid], policy: TiePolicy) -> String {
+    let mut csv = String::from("rank,hand,type,bid,winnings\n");
+    for (rank, hand_bid) in ranked(hands_bids, policy) {
+        csv += &format!(
+            "{},{},{},{},{}\n",
+            rank,
+            hand_bid.hand.cards,
+            hand_bid.hand.camel_type.name,
+            hand_bid.bid,
+            rank * hand_bid.bid
+        );
+    }
+    csv
+}
 
-impl CamelHand {
-    fn get_type(&self) -> CamelType {
-        let mut frequencies = self.0.iter().fold(HashMap::new(), |mut map, card| {
-            *map.entry(card).or_insert(0) += 1;
-            map
-        });
-        let (mode, mut mode_frequency): (CamelCard, i32) = frequencies
-            .iter()
-            .filter(|(k, _)| k != &&&CamelCard::Joker) // Avoid returning Joker if multiple modes
-            .map(|(k, v)| (**k, *v))
-            .max_by_key(|(_k, v)| *v)
-            .unwrap_or_else(|| {
-                // Joker was the only mode
-                (
-                    CamelCard::Joker,
-                    *frequencies.get(&CamelCard::Joker).unwrap(),
-                )
-            });
-        if mode != CamelCard::Joker {
-            // Can add jokers to frequency of mode to calculate highest score with same logic (I think)
-            let jokers: i32 = *frequencies.get(&CamelCard::Joker).unwrap_or(&0);
-            *frequencies.entry(&mode).or_insert(0) += jokers;
-            mode_frequency += jokers;
-            frequencies.insert(&CamelCard::Joker, 0);
+// Every hand in rank order, each explained against the hand ranked just below it
+#[allow(dead_code)]
+fn explain_ranked(hands_bids: &[HandBid], policy: TiePolicy) -> Vec<Explanation> {
+    let ranked = ranked(hands_bids, policy);
+    let below =
+        std::iter::once(None).chain(ranked.iter().map(|(_, hand_bid)| Some(&hand_bid.hand)));
+    ranked
+        .iter()
+        .zip(below)
+        .map(|((_, hand_bid), neighbour)| hand_bid.hand.explain(neighbour))
+        .collect()
+}
+
+static CAMEL_RULES: Lazy<Ruleset> = Lazy::new(Ruleset::camel);
+static CAMEL_RULES_WITH_JOKERS: Lazy<Ruleset> = Lazy::new(Ruleset::camel_with_jokers);
+
+// Everything that decides how hands of some card game rank against each other
+struct Ruleset {
+    // Cards from weakest to strongest
+    alphabet: Vec<char>,
+    hand_size: usize,
+    wilds: Vec<char>,
+    wild_rank: WildRank,
+    // Hand categories from weakest to strongest.  The first should fit any hand.
+    categories: Vec<Category>,
+    tie_break: TieBreak,
+}
+
+// Where wild cards rank when breaking ties
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum WildRank {
+    // Their usual place in the alphabet
+    Natural,
+    // Below every other card
+    Lowest,
+    // Above every other card
+    Highest,
+}
+
+// A category is fitted by any hand with at least these groups of equal cards, e.g.
+// [3, 2] for a full house, or [2, 2] for two pair. The shape is kept largest group first,
+// whatever order it was given in
+struct Category {
+    name: String,
+    shape: Vec<usize>,
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TieBreak {
+    // The first card, then the second card, and so on, as dealt
+    Positional,
+    // Cards sorted by how many of them there are and then by rank, so that the cards
+    // making up the category count first, like real poker
+    SortedHighCard,
+}
+
+impl Category {
+    fn new(name: &str, shape: &[usize]) -> Self {
+        let mut shape = shape.to_vec();
+        shape.sort_unstable_by(|a, b| b.cmp(a));
+        Self {
+            name: name.to_string(),
+            shape,
+        }
+    }
+}
+
+impl Ruleset {
+    fn camel() -> Self {
+        Self {
+            alphabet: "23456789TJQKA".chars().collect(),
+            hand_size: 5,
+            wilds: Vec::new(),
+            wild_rank: WildRank::Natural,
+            categories: vec![
+                Category::new("high card", &[1]),
+                Category::new("one pair", &[2]),
+                Category::new("two pair", &[2, 2]),
+                Category::new("three of a kind", &[3]),
+                Category::new("full house", &[3, 2]),
+                Category::new("four of a kind", &[4]),
+                Category::new("five of a kind", &[5]),
+            ],
+            tie_break: TieBreak::Positional,
+        }
+    }
+
+    fn camel_with_jokers() -> Self {
+        Self {
+            wilds: vec!['J'],
+            wild_rank: WildRank::Lowest,
+            ..Self::camel()
         }
+    }
 
-        if mode_frequency >= 5 {
-            CamelType::Five
-        } else if mode_frequency == 4 {
-            CamelType::Four
-        } else if mode_frequency == 3 {
-            if frequencies.values().contains(&2) {
-                CamelType::Full
-            } else {
-                CamelType::Three
+    fn hand(&self, input: &str) -> CamelHand {
+        let cards: Vec<char> = input.chars().collect();
+        if cards.len() != self.hand_size {
+            panic!("Invalid input");
+        }
+        let tie_break_cards = match self.tie_break {
+            TieBreak::Positional => cards.clone(),
+            TieBreak::SortedHighCard => {
+                let counts = cards.iter().counts();
+                cards
+                    .iter()
+                    .copied()
+                    .sorted_by_key(|card| (Reverse(counts[card]), Reverse(self.strength(*card))))
+                    .collect()
             }
-        } else if mode_frequency == 2 {
-            if frequencies
+        };
+        let (camel_type, wilds_as) = self.classify(&cards);
+        CamelHand {
+            cards: input.to_string(),
+            camel_type,
+            tie_break: tie_break_cards
                 .iter()
-                .filter(|(k, _)| **k != &mode)
-                .any(|(_, v)| v == &2)
-            {
-                CamelType::TwoPair
-            } else {
-                CamelType::Pair
+                .map(|card| self.strength(*card))
+                .collect(),
+            tie_break_cards,
+            frequencies: self.frequencies(cards.iter()),
+            wilds_as,
+        }
+    }
+
+    // How many of each card, most common (and then strongest) first
+    fn frequencies<'a, I: Iterator<Item = &'a char>>(&self, cards: I) -> Vec<(char, usize)> {
+        cards
+            .counts()
+            .into_iter()
+            .map(|(card, count)| (*card, count))
+            .sorted_by_key(|(card, count)| (Reverse(*count), Reverse(self.strength(*card))))
+            .collect()
+    }
+
+    // Rank of a card for breaking ties
+    fn strength(&self, card: char) -> usize {
+        let natural = self
+            .alphabet
+            .iter()
+            .position(|c| *c == card)
+            .expect("Invalid input");
+        if !self.wilds.contains(&card) {
+            return match self.wild_rank {
+                WildRank::Lowest => natural + 1,
+                _ => natural,
+            };
+        }
+        match self.wild_rank {
+            WildRank::Natural => natural,
+            WildRank::Lowest => 0,
+            WildRank::Highest => self.alphabet.len(),
+        }
+    }
+
+    // The strongest category the hand can make, with wild cards joining whichever
+    // groups are short of the category's shape.  Also gives the cards the wild cards
+    // stand in for, and how many of them stand in for each.
+    fn classify(&self, cards: &[char]) -> (CamelType, Vec<(char, usize)>) {
+        let wilds = cards.iter().filter(|c| self.wilds.contains(c)).count();
+        let groups = self.frequencies(cards.iter().filter(|c| !self.wilds.contains(c)));
+        let counts: Vec<usize> = groups.iter().map(|(_, count)| *count).collect();
+
+        let rank = (0..self.categories.len())
+            .rev()
+            .find(|rank| self.fits(&counts, wilds, &self.categories[*rank].shape))
+            .unwrap_or(0);
+
+        // Make up each group of the shape, starting new groups with the strongest card
+        // not already in the hand
+        let mut unused = self
+            .alphabet
+            .iter()
+            .rev()
+            .filter(|card| !self.wilds.contains(card) && !cards.contains(card));
+        let mut wilds_as: Vec<(char, usize)> = Vec::new();
+        let mut wilds_left = wilds;
+        for (i, wanted) in self.categories[rank].shape.iter().enumerate() {
+            let (card, have) = match groups.get(i) {
+                Some(group) => *group,
+                None => match unused.next() {
+                    Some(card) => (*card, 0),
+                    None => break,
+                },
+            };
+            let needed = usize::min(wanted.saturating_sub(have), wilds_left);
+            if needed > 0 {
+                wilds_as.push((card, needed));
+                wilds_left -= needed;
+            }
+        }
+        if wilds_left > 0 {
+            // Any spare wild cards make the biggest group bigger still
+            let biggest = groups
+                .first()
+                .or(wilds_as.first())
+                .map(|(card, _)| *card)
+                .or_else(|| unused.next().copied());
+            if let Some(biggest) = biggest {
+                match wilds_as.iter_mut().find(|(card, _)| *card == biggest) {
+                    Some((_, count)) => *count += wilds_left,
+                    None => wilds_as.push((biggest, wilds_left)),
+                }
             }
-        } else {
-            CamelType::High
         }
+
+        let camel_type = CamelType {
+            rank,
+            name: self.categories[rank].name.clone(),
+        };
+        (camel_type, wilds_as)
+    }
+
+    // Largest group against largest part of the shape, and so on, needs the fewest
+    // wild cards to make up the difference
+    fn fits(&self, groups: &[usize], wilds: usize, shape: &[usize]) -> bool {
+        let missing: usize = shape
+            .iter()
+            .enumerate()
+            .map(|(i, wanted)| wanted.saturating_sub(groups.get(i).copied().unwrap_or(0)))
+            .sum();
+        missing <= wilds && shape.iter().sum::<usize>() <= self.hand_size
+    }
+}
+
+// Hands compare by type and then by the tie break, so hands can be equal without
+// holding the same cards
+#[derive(Debug)]
+struct CamelHand {
+    cards: String,
+    camel_type: CamelType,
+    // Card strengths in tie-break order, and the cards themselves
+    tie_break: Vec<usize>,
+    tie_break_cards: Vec<char>,
+    frequencies: Vec<(char, usize)>,
+    wilds_as: Vec<(char, usize)>,
+}
+
+impl CamelHand {
+    fn get_type(&self) -> CamelType {
+        self.camel_type.clone()
     }
 
     fn create(input: &str) -> Self {
-        Self(input.chars().map(CamelCard::from_char).collect())
+        CAMEL_RULES.hand(input)
     }
 
     fn create_with_jokers(input: &str) -> Self {
-        Self(
-            input
-                .chars()
-                .map(CamelCard::from_char_with_jokers)
-                .collect(),
-        )
+        CAMEL_RULES_WITH_JOKERS.hand(input)
+    }
+
+    // Why this hand has its type, and what puts it above or below the neighbouring hand
+    // if given
+    fn explain(&self, neighbour: Option<&CamelHand>) -> Explanation {
+        let decision = match neighbour {
+            None => Decision::NoNeighbour,
+            Some(other) if self.camel_type != other.camel_type => Decision::ByType {
+                against: other.cards.clone(),
+                ordering: self.camel_type.cmp(&other.camel_type),
+            },
+            Some(other) => match (0..self.tie_break.len())
+                .find(|i| self.tie_break[*i] != other.tie_break[*i])
+            {
+                Some(position) => Decision::ByCard {
+                    against: other.cards.clone(),
+                    position,
+                    card: self.tie_break_cards[position],
+                    other_card: other.tie_break_cards[position],
+                    ordering: self.tie_break[position].cmp(&other.tie_break[position]),
+                },
+                None => Decision::Tied {
+                    against: other.cards.clone(),
+                },
+            },
+        };
+        Explanation {
+            hand: self.cards.clone(),
+            frequencies: self.frequencies.clone(),
+            wilds_as: self.wilds_as.clone(),
+            camel_type: self.get_type(),
+            decision,
+        }
     }
 }
 
-impl PartialOrd for CamelHand {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        let type_compare = self.get_type().cmp(&other.get_type());
+#[derive(Debug, PartialEq, Eq)]
+struct Explanation {
+    hand: String,
+    frequencies: Vec<(char, usize)>,
+    wilds_as: Vec<(char, usize)>,
+    camel_type: CamelType,
+    decision: Decision,
+}
 
-        if type_compare == Ordering::Equal {
-            // Fall back to comparing by cards, in order of hand
-            let (self_card, other_card) = self
-                .0
+#[derive(Debug, PartialEq, Eq)]
+enum Decision {
+    NoNeighbour,
+    ByType {
+        against: String,
+        ordering: Ordering,
+    },
+    // The first card in tie-break order that differs, counting from 0
+    ByCard {
+        against: String,
+        position: usize,
+        card: char,
+        other_card: char,
+        ordering: Ordering,
+    },
+    Tied {
+        against: String,
+    },
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}: {}", self.hand, self.camel_type.name)?;
+        let frequencies = self
+            .frequencies
+            .iter()
+            .map(|(card, count)| format!("{}x{}", card, count))
+            .join(" ");
+        writeln!(f, "  cards: {}", frequencies)?;
+        if !self.wilds_as.is_empty() {
+            let wilds_as = self
+                .wilds_as
                 .iter()
-                .zip(other.0.iter())
-                .find(|(c1, c2)| c1 != c2)
-                .unwrap();
-            Some(self_card.cmp(other_card))
-        } else {
-            Some(type_compare)
+                .map(|(card, count)| format!("{} as {}", count, card))
+                .join(", ");
+            writeln!(f, "  wilds: {}", wilds_as)?;
+        }
+        let verb = |ordering: &Ordering| match ordering {
+            Ordering::Less => "loses to",
+            Ordering::Equal => "ties with",
+            Ordering::Greater => "beats",
+        };
+        match &self.decision {
+            Decision::NoNeighbour => Ok(()),
+            Decision::ByType { against, ordering } => {
+                writeln!(f, "  {} {} on type", verb(ordering), against)
+            }
+            Decision::ByCard {
+                against,
+                position,
+                card,
+                other_card,
+                ordering,
+            } => writeln!(
+                f,
+                "  {} {} at tie-break card {}: {} against {}",
+                verb(ordering),
+                against,
+                position + 1,
+                card,
+                other_card
+            ),
+            Decision::Tied { against } => writeln!(f, "  {} {}", verb(&Ordering::Equal), against),
         }
     }
 }
 
-#[derive(PartialEq, Eq, PartialOrd, Ord, Debug)]
-enum CamelType {
-    High,
-    Pair,
-    TwoPair,
-    Three,
-    Full,
-    Four,
-    Five,
-}
-
-#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy)]
-enum CamelCard {
-    Joker,
-    Two,
-    Three,
-    Four,
-    Five,
-    Six,
-    Seven,
-    Eight,
-    Nine,
-    Ten,
-    Jack,
-    Queen,
-    King,
-    Ace,
-}
-
-impl CamelCard {
-    fn from_char(c: char) -> Self {
-        match c {
-            '2' => Self::Two,
-            '3' => Self::Three,
-            '4' => Self::Four,
-            '5' => Self::Five,
-            '6' => Self::Six,
-            '7' => Self::Seven,
-            '8' => Self::Eight,
-            '9' => Self::Nine,
-            'T' => Self::Ten,
-            'J' => Self::Jack,
-            'Q' => Self::Queen,
-            'K' => Self::King,
-            'A' => Self::Ace,
-            _ => panic!("Invalid input"),
-        }
+impl Ord for CamelHand {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.camel_type
+            .cmp(&other.camel_type)
+            .then_with(|| self.tie_break.cmp(&other.tie_break))
     }
+}
 
-    fn from_char_with_jokers(c: char) -> Self {
-        if c == 'J' {
-            Self::Joker
-        } else {
-            Self::from_char(c)
-        }
+impl PartialOrd for CamelHand {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for CamelHand {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
     }
 }
 
+impl Eq for CamelHand {}
+
+// Rank is the position in the ruleset's list of categories
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone)]
+struct CamelType {
+    rank: usize,
+    name: String,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -205,9 +490,9 @@ mod tests {
     #[test]
     fn check_types() {
         let hand1 = CamelHand::create("32T3K");
-        assert_eq!(hand1.get_type(), CamelType::Pair);
+        assert_eq!(hand1.get_type().name, "one pair");
         let hand2 = CamelHand::create("QQQJA");
-        assert_eq!(hand2.get_type(), CamelType::Three);
+        assert_eq!(hand2.get_type().name, "three of a kind");
         assert!(hand2 > hand1);
     }
 
@@ -222,4 +507,170 @@ mod tests {
         let input_string = "32T3K 765\nT55J5 684\nKK677 28\nKTJJT 220\nQQQJA 483";
         assert_eq!(part2(input_string), 5905)
     }
+
+    #[test]
+    fn check_equal_hands() {
+        let hand1 = CamelHand::create("KK677");
+        let hand2 = CamelHand::create("KK677");
+        assert_eq!(hand1.cmp(&hand2), Ordering::Equal);
+        assert!(CamelHand::create("KK677") > CamelHand::create("KK676"));
+    }
+
+    #[test]
+    fn check_duplicate_hands_keep_their_bids() {
+        let input_string = "32T3K 765\nKK677 28\n32T3K 10\nQQQJA 483";
+        let hands_bids = read_hands(input_string, CamelHand::create);
+        // Input order: 32T3K (765) ranked 1, 32T3K (10) ranked 2
+        assert_eq!(
+            winnings(&hands_bids, TiePolicy::InputOrder),
+            765 + 10 * 2 + 28 * 3 + 483 * 4
+        );
+        // Shared rank: both 32T3K hands ranked 1, and the next hand still ranked 3
+        let ranks = ranked(&hands_bids, TiePolicy::SharedRank)
+            .iter()
+            .map(|(rank, hand_bid)| (*rank, hand_bid.bid))
+            .collect_vec();
+        assert_eq!(ranks, vec![(1, 765), (1, 10), (3, 28), (4, 483)]);
+        assert_eq!(
+            winnings(&hands_bids, TiePolicy::SharedRank),
+            765 + 10 + 28 * 3 + 483 * 4
+        );
+    }
+
+    #[test]
+    fn check_joker_types() {
+        let types = [
+            "32T3K", "T55J5", "KK677", "KTJJT", "QQQJA", "JJJJJ", "2345J", "22JJ3",
+        ]
+        .map(|hand| CamelHand::create_with_jokers(hand).get_type().name);
+        assert_eq!(
+            types,
+            [
+                "one pair",
+                "four of a kind",
+                "two pair",
+                "four of a kind",
+                "four of a kind",
+                "five of a kind",
+                "one pair",
+                "four of a kind"
+            ]
+        );
+        // Jokers are weakest when breaking ties
+        assert!(CamelHand::create_with_jokers("JKKK2") < CamelHand::create_with_jokers("QQQQ2"));
+        assert!(CamelHand::create("JKKK3") > CamelHand::create("2KKK3"));
+    }
+
+    #[test]
+    fn check_custom_ruleset() {
+        // Three-card hands where a run of two pairs can't happen, twos are wild and rank
+        // highest, and ties look at the pair before the odd card
+        let rules = Ruleset {
+            alphabet: "23456789TJQKA".chars().collect(),
+            hand_size: 3,
+            wilds: vec!['2'],
+            wild_rank: WildRank::Highest,
+            categories: vec![
+                Category::new("nothing", &[1]),
+                Category::new("pair", &[2]),
+                Category::new("trips", &[3]),
+            ],
+            tie_break: TieBreak::SortedHighCard,
+        };
+        assert_eq!(rules.hand("2KA").get_type().name, "pair");
+        assert_eq!(rules.hand("222").get_type().name, "trips");
+        // Pair of fives beats pair of fours whatever the odd card
+        assert!(rules.hand("A55") > rules.hand("44K"));
+        // Wild twos break ties above aces
+        assert!(rules.hand("2KA") > rules.hand("AKA"));
+        // The same cards in a different order tie
+        assert_eq!(rules.hand("K5K").cmp(&rules.hand("5KK")), Ordering::Equal);
+    }
+
+    #[test]
+    fn check_unsorted_shape() {
+        let mut rules = Ruleset::camel();
+        rules.categories[4] = Category::new("full house", &[2, 3]);
+        assert_eq!(rules.hand("KKKQQ").get_type().name, "full house");
+        assert_eq!(rules.hand("KKQQJ").get_type().name, "two pair");
+    }
+
+    #[test]
+    fn check_category_order_matters() {
+        // If two pair outranks three of a kind, a wild card should make two pair
+        let mut rules = Ruleset::camel_with_jokers();
+        rules.categories.swap(2, 3);
+        assert_eq!(rules.hand("33J45").get_type().name, "two pair");
+        assert_eq!(
+            Ruleset::camel_with_jokers().hand("33J45").get_type().name,
+            "three of a kind"
+        );
+    }
+
+    #[test]
+    fn check_explain() {
+        let hand = CamelHand::create_with_jokers("KTJJT");
+        let neighbour = CamelHand::create_with_jokers("QQQJA");
+        let explanation = hand.explain(Some(&neighbour));
+        assert_eq!(explanation.frequencies, vec![('T', 2), ('J', 2), ('K', 1)]);
+        assert_eq!(explanation.wilds_as, vec![('T', 2)]);
+        assert_eq!(explanation.camel_type.name, "four of a kind");
+        assert_eq!(
+            explanation.decision,
+            Decision::ByCard {
+                against: "QQQJA".to_string(),
+                position: 0,
+                card: 'K',
+                other_card: 'Q',
+                ordering: Ordering::Greater
+            }
+        );
+        assert_eq!(
+            explanation.to_string(),
+            "KTJJT: four of a kind\n  cards: Tx2 Jx2 Kx1\n  wilds: 2 as T\n  beats QQQJA at tie-break card 1: K against Q\n"
+        );
+
+        let all_jokers = CamelHand::create_with_jokers("JJJJJ").explain(None);
+        assert_eq!(all_jokers.wilds_as, vec![('A', 5)]);
+        assert_eq!(all_jokers.decision, Decision::NoNeighbour);
+
+        let pair = CamelHand::create_with_jokers("2345J");
+        assert_eq!(pair.explain(None).wilds_as, vec![('5', 1)]);
+        assert_eq!(
+            pair.explain(Some(&hand)).decision,
+            Decision::ByType {
+                against: "KTJJT".to_string(),
+                ordering: Ordering::Less
+            }
+        );
+        let same = CamelHand::create("2345J");
+        assert_eq!(
+            same.explain(Some(&CamelHand::create("2345J"))).decision,
+            Decision::Tied {
+                against: "2345J".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn check_rank_table() {
+        let input_string = "32T3K 765\nT55J5 684\nKK677 28\nKTJJT 220\nQQQJA 483";
+        let hands_bids = read_hands(input_string, CamelHand::create_with_jokers);
+        assert_eq!(
+            rank_table_csv(&hands_bids, TiePolicy::InputOrder),
+            "rank,hand,type,bid,winnings
+1,32T3K,one pair,765,765
+2,KK677,two pair,28,56
+3,T55J5,four of a kind,684,2052
+4,QQQJA,four of a kind,483,1932
+5,KTJJT,four of a kind,220,1100
+"
+        );
+        let explanations = explain_ranked(&hands_bids, TiePolicy::InputOrder);
+        assert_eq!(explanations[0].decision, Decision::NoNeighbour);
+        assert_eq!(
+            explanations[3].to_string(),
+            "QQQJA: four of a kind\n  cards: Qx3 Ax1 Jx1\n  wilds: 1 as Q\n  beats T55J5 at tie-break card 1: Q against T\n"
+        );
+    }
 }
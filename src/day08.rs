@@ -1,4 +1,6 @@
-use num::Integer;
+use crate::cycle::History;
+use crate::number_theory::Progression;
+use itertools::Itertools;
 use regex::Regex;
 use std::collections::HashMap;
 use std::str::{Chars, Lines};
@@ -10,14 +12,17 @@ pub fn day08(input_lines: &str) -> (String, String) {
     let graph = DesertGraph::create(lines);
 
     let answer1 = part1(directions.clone(), &graph, "AAA");
-    let answer2 = part2(directions, &graph);
-    (format!("{}", answer1), format!("{:?}", answer2))
+    let answer2 = match part2(directions, &graph) {
+        Some(steps) => format!("{}", steps),
+        None => String::from("never"),
+    };
+    (format!("{}", answer1), answer2)
 }
 
 fn part1(directions: Chars<'_>, graph: &DesertGraph, start: &'static str) -> u64 {
     let mut steps = 0;
-    let mut current = String::from(start);
-    let end = "ZZZ";
+    let mut current = graph.id(start).expect("No start node");
+    let end = graph.id("ZZZ").expect("No end node");
     let directions = directions.cycle();
 
     for direction in directions {
@@ -25,58 +30,103 @@ fn part1(directions: Chars<'_>, graph: &DesertGraph, start: &'static str) -> u64
             break;
         }
 
-        current = graph.get_hop(&current, direction);
+        current = graph.get_hop(current, direction);
         steps += 1;
     }
     steps
 }
 
-fn part2(directions: Chars<'_>, graph: &DesertGraph) -> u64 {
-    // Let's make some ASSUMPTIONS:
-    // For each 'A' node, you can follow directions to reach exactly one 'Z' node, after which a cycle repeats.
-    // All the cycles start in exacly the same position - on the node after the initial 'A' node.
-    // e.g.
-    // A1Z1Z1Z1Z1Z1Z - cycle length 2
-    // A12Z12Z12Z12Z - cycle length 3
-    // A123Z123Z123Z - cycle length 4
-
-    // With these assumptions, the position where all the 'Z' states line up is the LCM of the cycle lengths
-    // For the example above, it's 12
-    let start_states = graph
-        .nodes
-        .keys()
-        .filter(|name| name.ends_with('A'))
-        .cloned();
-    let mut answer: u64 = 0;
-
-    for start in start_states {
-        let mut current = start;
-        let mut steps = 0;
-        for direction in directions.clone().cycle() {
-            if current.ends_with('Z') {
-                break;
-            }
+fn part2(directions: Chars<'_>, graph: &DesertGraph) -> Option<u64> {
+    let directions: Vec<char> = directions.collect();
+    // With no directions no ghost ever moves
+    if directions.is_empty() {
+        return None;
+    }
+    let walks = graph
+        .ids()
+        .filter(|id| graph.name(*id).ends_with('A'))
+        .map(|start| GhostWalk::trace(graph, &directions, start))
+        .collect_vec();
+    first_simultaneous_end(&walks)
+}
 
-            current = graph.get_hop(&current, direction);
-            steps += 1
-        }
-        if answer == 0 {
-            answer = steps;
-        } else {
-            answer = answer.lcm(&steps);
+// Where one ghost goes.  Its states of (node, position in the directions) must
+// eventually loop: the first `tail` steps lead into a cycle of `period` steps that then
+// repeats forever.  The steps it stands on a 'Z' node are then some one-off steps in the
+// tail, plus every `period` steps on from each 'Z' in the cycle.
+#[derive(Debug, PartialEq, Eq)]
+struct GhostWalk {
+    tail: usize,
+    period: usize,
+    tail_ends: Vec<usize>,
+    cycle_ends: Vec<usize>,
+}
+
+impl GhostWalk {
+    fn trace(graph: &DesertGraph, directions: &[char], start: u32) -> Self {
+        let walk = History::detect((start, 0), |(node, i)| {
+            (
+                graph.get_hop(node, directions[i]),
+                (i + 1) % directions.len(),
+            )
+        });
+        let is_end = |(node, _): &(u32, usize)| graph.name(*node).ends_with('Z');
+        Self {
+            tail: walk.cycle.tail,
+            period: walk.cycle.period,
+            tail_ends: walk.tail_positions(is_end),
+            cycle_ends: walk.cycle_positions(is_end),
         }
     }
-    answer
+
+    // Every step this ghost is on a 'Z' node
+    fn end_steps(&self) -> Vec<Progression> {
+        let period: i128 = self.period.try_into().unwrap();
+        self.tail_ends
+            .iter()
+            .map(|step| Progression::single((*step).try_into().unwrap()))
+            .chain(
+                self.cycle_ends
+                    .iter()
+                    .map(|step| Progression::new((*step).try_into().unwrap(), period)),
+            )
+            .collect()
+    }
+}
+
+// The earliest step that every ghost is on a 'Z' node, trying each combination of
+// their 'Z' steps in turn and combining the progressions with the CRT
+fn first_simultaneous_end(walks: &[GhostWalk]) -> Option<u64> {
+    let mut candidates = vec![Progression::new(0, 1)];
+    for walk in walks {
+        candidates = candidates
+            .iter()
+            .cartesian_product(walk.end_steps().iter())
+            .filter_map(|(candidate, end_step)| candidate.intersect(end_step))
+            .unique()
+            .collect();
+    }
+
+    candidates
+        .iter()
+        .map(|progression| progression.start.try_into().unwrap())
+        .min()
 }
 
+// Node labels are interned to dense ids, with the left and right neighbours of node n
+// at 2n and 2n + 1 in one flat array
 struct DesertGraph {
-    nodes: HashMap<String, Node>,
+    names: Vec<String>,
+    ids: HashMap<String, u32>,
+    neighbours: Vec<u32>,
 }
 
 impl DesertGraph {
     fn new() -> Self {
         Self {
-            nodes: HashMap::new(),
+            names: Vec::new(),
+            ids: HashMap::new(),
+            neighbours: Vec::new(),
         }
     }
 
@@ -86,33 +136,111 @@ impl DesertGraph {
 
         for line in input_lines {
             for (_, [parent, left, right]) in node_regex.captures_iter(line).map(|c| c.extract()) {
-                graph
-                    .nodes
-                    .entry(String::from(parent))
-                    .or_insert_with(|| Node {
-                        left: String::from(left),
-                        right: String::from(right),
-                    });
+                let parent = graph.intern(parent);
+                let (left, right) = (graph.intern(left), graph.intern(right));
+                let slot = 2 * parent as usize;
+                if graph.neighbours[slot] == u32::MAX {
+                    graph.neighbours[slot] = left;
+                    graph.neighbours[slot + 1] = right;
+                }
             }
         }
+        if graph.neighbours.contains(&u32::MAX) {
+            panic!("Some nodes lead nowhere");
+        }
 
         graph
     }
 
-    fn get_hop(&self, current_node: &str, direction: char) -> String {
-        let current_node = self.nodes.get(current_node).unwrap();
+    fn intern(&mut self, name: &str) -> u32 {
+        if let Some(id) = self.ids.get(name) {
+            return *id;
+        }
+        let id: u32 = self.names.len().try_into().expect("Too many nodes");
+        self.names.push(String::from(name));
+        self.ids.insert(String::from(name), id);
+        self.neighbours.extend([u32::MAX, u32::MAX]);
+        id
+    }
+
+    fn id(&self, name: &str) -> Option<u32> {
+        self.ids.get(name).copied()
+    }
+
+    fn name(&self, id: u32) -> &str {
+        &self.names[id as usize]
+    }
+
+    fn ids(&self) -> impl Iterator<Item = u32> {
+        0..self.names.len() as u32
+    }
+
+    fn get_hop(&self, current_node: u32, direction: char) -> u32 {
+        let slot = 2 * current_node as usize;
         match direction {
-            'L' => current_node.left.clone(),
-            'R' => current_node.right.clone(),
+            'L' => self.neighbours[slot],
+            'R' => self.neighbours[slot + 1],
             _ => panic!("That's not a direction"),
         }
     }
 }
 
-#[derive(Debug)]
-struct Node {
-    left: String,
-    right: String,
+// Jump tables for following the directions a long way.  passes[k][n] is where node n
+// ends up after 2^k complete passes through the directions, so the node after N steps
+// takes O(log N) jumps plus at most one partial pass.  Not used by the solvers, which
+// only need where each Z is hit; it's for asking where a ghost is at a given step.
+#[allow(dead_code)]
+struct Route {
+    directions: Vec<char>,
+    passes: Vec<Vec<u32>>,
+}
+
+#[allow(dead_code)]
+impl Route {
+    fn compile(graph: &DesertGraph, directions: &[char]) -> Self {
+        let one_pass: Vec<u32> = graph
+            .ids()
+            .map(|id| {
+                directions
+                    .iter()
+                    .fold(id, |node, direction| graph.get_hop(node, *direction))
+            })
+            .collect();
+        // Enough levels for any u64 step count
+        let mut passes = vec![one_pass];
+        for _ in 1..64 {
+            let last = passes.last().unwrap();
+            let doubled = last.iter().map(|node| last[*node as usize]).collect();
+            passes.push(doubled);
+        }
+        Self {
+            directions: directions.to_vec(),
+            passes,
+        }
+    }
+
+    // The node after a full pass through the directions
+    fn after_pass(&self, node: u32) -> u32 {
+        self.passes[0][node as usize]
+    }
+
+    // With no directions there's nowhere to go, so the ghost stays at the start
+    fn position_after(&self, graph: &DesertGraph, start: u32, steps: u64) -> u32 {
+        let length = self.directions.len() as u64;
+        if length == 0 {
+            return start;
+        }
+        let (full_passes, remainder) = (steps / length, steps % length);
+        let mut node = start;
+        for (level, table) in self.passes.iter().enumerate() {
+            if full_passes >> level & 1 == 1 {
+                node = table[node as usize];
+            }
+        }
+        self.directions[..remainder as usize]
+            .iter()
+            .fold(node, |node, direction| graph.get_hop(node, *direction))
+    }
 }
 
 #[cfg(test)]
@@ -126,6 +254,244 @@ mod tests {
         let directions = lines.next().unwrap().chars();
         lines.next().unwrap(); // Skip blank line
         let graph = DesertGraph::create(lines);
-        assert_eq!(part2(directions, &graph), 6)
+        assert_eq!(part2(directions, &graph), Some(6))
+    }
+
+    #[test]
+    fn check_day08_part2_offset_cycles() {
+        // 11A reaches Z after 3 steps and then every 2; 22A after 2 steps and then every 4
+        let input = "L\n\n11A = (11B, XXX)\n11B = (11C, XXX)\n11C = (11Z, XXX)\n11Z = (11C, XXX)\n22A = (22B, XXX)\n22B = (22Z, XXX)\n22Z = (22C, XXX)\n22C = (22D, XXX)\n22D = (22E, XXX)\n22E = (22Z, XXX)\nXXX = (XXX, XXX)";
+        let mut lines = input.lines();
+        let directions = lines.next().unwrap().chars();
+        lines.next().unwrap(); // Skip blank line
+        let graph = DesertGraph::create(lines);
+        // 11A: 3, 5, 7, 9, ...  22A: 2, 6, 10, ...  (an LCM of cycle lengths would give 4)
+        assert_eq!(part2(directions, &graph), None);
+    }
+
+    #[test]
+    fn check_day08_part2_non_coprime_offsets() {
+        // 11A: Z at 3, 5, 7, 9, ...  22A: Z at 1, 4, 7, 10, ...
+        let input = "L\n\n11A = (11B, XXX)\n11B = (11C, XXX)\n11C = (11Z, XXX)\n11Z = (11C, XXX)\n22A = (22Z, XXX)\n22Z = (22B, XXX)\n22B = (22C, XXX)\n22C = (22Z, XXX)\nXXX = (XXX, XXX)";
+        let mut lines = input.lines();
+        let directions = lines.next().unwrap().chars();
+        lines.next().unwrap(); // Skip blank line
+        let graph = DesertGraph::create(lines);
+        assert_eq!(part2(directions, &graph), Some(7));
+    }
+
+    #[test]
+    fn check_day08_part2_walks_stop() {
+        // A ghost that leaves its only 'Z' node, one that never reaches one, and no
+        // directions to follow at all
+        let check = |input: &str, expected: Option<u64>| {
+            let mut lines = input.lines();
+            let directions = lines.next().unwrap().chars();
+            lines.next().unwrap(); // Skip blank line
+            let graph = DesertGraph::create(lines);
+            assert_eq!(part2(directions, &graph), expected);
+        };
+        check("L\n\nAAA = (ZZZ, ZZZ)\nZZZ = (ZZZ, ZZZ)\n11A = (11Z, 11Z)\n11Z = (XXX, XXX)\nXXX = (XXX, XXX)", Some(1));
+        check(
+            "L\n\n11A = (11B, 11B)\n11B = (11A, 11A)\nXXX = (XXX, XXX)",
+            None,
+        );
+        check("\n\n11A = (11Z, 11Z)\n11Z = (11Z, 11Z)", None);
+    }
+
+    fn example_graph(input: &str) -> (Vec<char>, DesertGraph) {
+        let mut lines = input.lines();
+        let directions = lines.next().unwrap().chars().collect();
+        lines.next().unwrap(); // Skip blank line
+        (directions, DesertGraph::create(lines))
+    }
+
+    #[test]
+    fn check_ghost_walk() {
+        // One step leads into the loop 11Z, 11C, 11Y, which starts on its only Z
+        let (directions, graph) = example_graph("L\n\n11A = (11Z, XXX)\n11Z = (11C, XXX)\n11C = (11Y, XXX)\n11Y = (11Z, XXX)\nXXX = (XXX, XXX)");
+        let walk = GhostWalk::trace(&graph, &directions, graph.id("11A").unwrap());
+        assert_eq!(
+            walk,
+            GhostWalk {
+                tail: 1,
+                period: 3,
+                tail_ends: vec![],
+                cycle_ends: vec![1],
+            }
+        );
+
+        // Z nodes only before the loop, at steps 1 and 2
+        let (directions, graph) = example_graph(
+            "LR\n\n11A = (11Z, XXX)\n11Z = (XXX, 22Z)\n22Z = (XXX, XXX)\nXXX = (XXX, XXX)",
+        );
+        let walk = GhostWalk::trace(&graph, &directions, graph.id("11A").unwrap());
+        assert_eq!(walk.tail_ends, vec![1, 2]);
+        assert_eq!(walk.cycle_ends, vec![]);
+    }
+
+    #[test]
+    fn check_first_simultaneous_end() {
+        let walk = |tail, period, tail_ends: &[usize], cycle_ends: &[usize]| GhostWalk {
+            tail,
+            period,
+            tail_ends: tail_ends.to_vec(),
+            cycle_ends: cycle_ends.to_vec(),
+        };
+        // Two Zs in one cycle: 4, 6, 9, 11, 14, ... and 3, 6, 9, ...
+        let walks = [walk(2, 5, &[], &[4, 6]), walk(0, 3, &[], &[3])];
+        assert_eq!(first_simultaneous_end(&walks), Some(6));
+        // Only meeting in one ghost's tail
+        let walks = [walk(10, 4, &[7], &[12]), walk(0, 7, &[], &[7])];
+        assert_eq!(first_simultaneous_end(&walks), Some(7));
+        // A ghost that never reaches a Z
+        let walks = [walk(0, 2, &[], &[2]), walk(3, 1, &[], &[])];
+        assert_eq!(first_simultaneous_end(&walks), None);
+        // Large, non-coprime periods
+        let walks = [
+            walk(0, 1_000_000_007 * 6, &[], &[5]),
+            walk(0, 1_000_000_007 * 4, &[], &[1]),
+        ];
+        assert_eq!(first_simultaneous_end(&walks), None);
+        let walks = [
+            walk(0, 1_000_000_007 * 6, &[], &[5]),
+            walk(0, 1_000_000_007 * 4, &[], &[5]),
+        ];
+        assert_eq!(first_simultaneous_end(&walks), Some(5));
+    }
+
+    #[test]
+    fn check_position_after() {
+        let (directions, graph) = example_graph("LR\n\n11A = (11B, XXX)\n11B = (XXX, 11Z)\n11Z = (11B, XXX)\n22A = (22B, XXX)\n22B = (22C, 22C)\n22C = (22Z, 22Z)\n22Z = (22B, 22B)\nXXX = (XXX, XXX)");
+        let route = Route::compile(&graph, &directions);
+        for start in graph.ids() {
+            let mut node = start;
+            for steps in 0..50 {
+                assert_eq!(route.position_after(&graph, start, steps), node);
+                node = graph.get_hop(node, directions[steps as usize % directions.len()]);
+            }
+        }
+        let start = graph.id("22A").unwrap();
+        assert_eq!(graph.name(route.after_pass(start)), "22C");
+        // 22B, 22C, 22Z repeat forever, and 10^18 - 1 is a multiple of 3
+        assert_eq!(
+            graph.name(route.position_after(&graph, start, 1_000_000_000_000_000_000)),
+            "22B"
+        );
+        let still = Route::compile(&graph, &[]);
+        assert_eq!(still.position_after(&graph, start, 12), start);
+    }
+
+    // The String-keyed graph that the interned one replaced, kept to check and time
+    // against
+    mod reference {
+        use super::*;
+
+        pub struct StringGraph {
+            nodes: HashMap<String, (String, String)>,
+        }
+
+        impl StringGraph {
+            pub fn create(input_lines: Lines<'_>) -> Self {
+                let node_regex = Regex::new(r"([A-Z0-9]+) = \(([A-Z0-9]+), ([A-Z0-9]+)\)").unwrap();
+                let mut nodes = HashMap::new();
+                for line in input_lines {
+                    for (_, [parent, left, right]) in
+                        node_regex.captures_iter(line).map(|c| c.extract())
+                    {
+                        nodes
+                            .entry(String::from(parent))
+                            .or_insert_with(|| (String::from(left), String::from(right)));
+                    }
+                }
+                Self { nodes }
+            }
+
+            fn get_hop(&self, current_node: &str, direction: char) -> String {
+                let current_node = self.nodes.get(current_node).unwrap();
+                match direction {
+                    'L' => current_node.0.clone(),
+                    'R' => current_node.1.clone(),
+                    _ => panic!("That's not a direction"),
+                }
+            }
+        }
+
+        pub fn part1(directions: &[char], graph: &StringGraph) -> u64 {
+            let mut steps = 0;
+            let mut current = String::from("AAA");
+            for direction in directions.iter().cycle() {
+                if current == "ZZZ" {
+                    break;
+                }
+                current = graph.get_hop(&current, *direction);
+                steps += 1;
+            }
+            steps
+        }
+
+        pub fn part2(directions: &[char], graph: &StringGraph) -> Option<u64> {
+            let walks = graph
+                .nodes
+                .keys()
+                .filter(|name| name.ends_with('A'))
+                .map(|start| {
+                    let walk = History::detect((start.clone(), 0), |(node, i)| {
+                        (
+                            graph.get_hop(&node, directions[i]),
+                            (i + 1) % directions.len(),
+                        )
+                    });
+                    let is_end = |(node, _): &(String, usize)| node.ends_with('Z');
+                    GhostWalk {
+                        tail: walk.cycle.tail,
+                        period: walk.cycle.period,
+                        tail_ends: walk.tail_positions(is_end),
+                        cycle_ends: walk.cycle_positions(is_end),
+                    }
+                })
+                .collect_vec();
+            first_simultaneous_end(&walks)
+        }
+    }
+
+    // cargo test --release bench_day08 -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn bench_day08_against_reference() {
+        let input = std::fs::read_to_string("inputs/8").expect("Can't open/read input file");
+        let mut lines = input.lines();
+        let directions: Vec<char> = lines.next().unwrap().chars().collect();
+        lines.next().unwrap(); // Skip blank line
+        let rounds = 5;
+
+        let start = std::time::Instant::now();
+        let mut answers = Vec::new();
+        for _ in 0..rounds {
+            let graph = reference::StringGraph::create(lines.clone());
+            answers = vec![
+                Some(reference::part1(&directions, &graph)),
+                reference::part2(&directions, &graph),
+            ];
+        }
+        let reference_time = start.elapsed() / rounds;
+
+        let start = std::time::Instant::now();
+        let mut interned_answers = Vec::new();
+        for _ in 0..rounds {
+            let graph = DesertGraph::create(lines.clone());
+            let directions_string: String = directions.iter().collect();
+            interned_answers = vec![
+                Some(part1(directions_string.chars(), &graph, "AAA")),
+                part2(directions_string.chars(), &graph),
+            ];
+        }
+        let interned_time = start.elapsed() / rounds;
+
+        println!(
+            "String graph: {:?}, interned graph: {:?}",
+            reference_time, interned_time
+        );
+        assert_eq!(answers, interned_answers);
     }
 }
@@ -1,46 +1,37 @@
-use itertools::rev;
+use crate::sequence::DifferenceTable;
+use num::BigInt;
 
 pub fn day09(input_lines: &str) -> (String, String) {
-    let inputs = input_lines.lines().map(|line| {
-        line.split_ascii_whitespace()
-            .map(|n| n.parse::<i32>().unwrap())
-    });
-    let answer1 = part1(inputs);
-    let inputs_2 = input_lines.lines().map(|line| {
-        line.split_ascii_whitespace()
-            .map(|n| n.parse::<i32>().unwrap())
-    });
-    let answer2 = part2(inputs_2);
-    (format!("{}", answer1), format!("{}", answer2))
+    // Blank lines have nothing to extrapolate, so add nothing.  Any other line that
+    // can't be extrapolated is reported as the answer.
+    let tables: Result<Vec<DifferenceTable>, String> = input_lines
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            DifferenceTable::new(
+                line.split_ascii_whitespace()
+                    .map(|n| n.parse::<i64>().unwrap()),
+            )
+            .map_err(|e| format!("Can't extrapolate line {}: {}", index + 1, e))
+        })
+        .collect();
+    match tables {
+        Ok(tables) => {
+            let answer1 = part1(&tables);
+            let answer2 = part2(&tables);
+            (format!("{}", answer1), format!("{}", answer2))
+        }
+        Err(e) => (e.clone(), e),
+    }
 }
 
-fn part1<I, J>(sequences: I) -> i32
-where
-    I: Iterator<Item = J>,
-    J: Iterator<Item = i32>,
-{
-    sequences.map(next_value).sum()
+fn part1(tables: &[DifferenceTable]) -> BigInt {
+    tables.iter().map(|table| table.forward(1)).sum()
 }
 
-fn part2<I, J>(sequences: I) -> i32
-where
-    I: Iterator<Item = J>,
-    J: DoubleEndedIterator<Item = i32>,
-{
-    sequences.map(rev).map(next_value).sum()
-}
-
-fn next_value<I>(input: I) -> i32
-where
-    I: Iterator<Item = i32>,
-{
-    let mut final_vals: Vec<i32> = Vec::new();
-    let mut next_sequence: Vec<i32> = input.collect();
-    while !next_sequence.iter().all(|n| *n == 0) {
-        final_vals.push(*next_sequence.last().unwrap());
-        next_sequence = next_sequence.windows(2).map(|s| s[1] - s[0]).collect();
-    }
-    final_vals.iter().sum()
+fn part2(tables: &[DifferenceTable]) -> BigInt {
+    tables.iter().map(|table| table.backward(1)).sum()
 }
 
 #[cfg(test)]
@@ -49,7 +40,23 @@ mod tests {
 
     #[test]
     fn check_day09_part1() {
-        let input: Vec<i32> = vec![1, 3, 6, 10, 15, 21];
-        assert_eq!(next_value(input.into_iter()), 28)
+        let table = DifferenceTable::new([1, 3, 6, 10, 15, 21]).unwrap();
+        assert_eq!(table.forward(1), BigInt::from(28))
+    }
+
+    #[test]
+    fn check_day09_both() {
+        let input = "0 3 6 9 12 15\n1 3 6 10 15 21\n10 13 16 21 30 45";
+        assert_eq!(day09(input), ("114".to_string(), "2".to_string()))
+    }
+
+    #[test]
+    fn check_day09_short_and_blank_lines() {
+        assert_eq!(day09("5"), ("5".to_string(), "5".to_string()));
+        assert_eq!(day09("1 2 3\n\n1 2 3"), ("8".to_string(), "0".to_string()));
+        assert_eq!(
+            day09("1 2 3\n1 3").0,
+            "Can't extrapolate line 2: Differences of these 2 values never reach zero, so no polynomial fits"
+        );
     }
 }
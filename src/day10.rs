@@ -1,38 +1,31 @@
+use crate::geometry::{self, Polygon};
 use std::str::Lines;
 
 pub fn day10(input_lines: &str) -> (String, String) {
-    let lines = input_lines.lines();
-    let answer1 = part1(lines);
-    let answer2 = 0;
+    let answer1 = part1(input_lines.lines());
+    let answer2 = part2(input_lines.lines());
     (format!("{}", answer1), format!("{}", answer2))
 }
 
 fn part1(lines: Lines<'_>) -> u32 {
     let field = Field::create(lines);
+    let length: u32 = field.main_loop().len().try_into().unwrap();
+    length / 2
+}
 
-    let mut prev = field.find_start();
-    let mut current = prev.clone();
-    let mut length = 1;
-
-    loop {
-        // Find next pipe in loop
-        for neighbours in &current.openings {
-            if let Some(maybe) = field.get_pipe(neighbours.x, neighbours.y) {
-                if maybe != prev && current.connects_to(&maybe) {
-                    prev = current;
-                    current = maybe;
-                    length += 1;
-                    break;
-                }
-            }
-        }
-        if current.symbol == 'S' {
-            break;
-        }
+fn part2(lines: Lines<'_>) -> i128 {
+    // The loop is a lattice polygon whose boundary points are exactly the pipes in
+    // the loop, so Pick's theorem counts the tiles inside
+    let field = Field::create(lines);
+    let vertices: Vec<geometry::Point> = field
+        .main_loop()
+        .iter()
+        .map(|point| geometry::Point::new(point.x.into(), point.y.into()))
+        .collect();
+    if vertices.is_empty() {
+        return 0;
     }
-
-    println!("Length of loop {}", length);
-    (length as f64 / 2.0).floor() as u32
+    Polygon::new(vertices).interior_points()
 }
 
 struct Field(Vec<Vec<char>>);
@@ -46,15 +39,43 @@ impl Field {
         Self(line_vec)
     }
 
-    fn find_start(&self) -> Pipe {
+    fn find_start(&self) -> Option<Pipe> {
         for (j, line) in self.0.iter().enumerate() {
             for (i, c) in line.iter().enumerate() {
                 if *c == 'S' {
-                    return Pipe::new(i.try_into().unwrap(), j.try_into().unwrap(), 'S');
+                    return Some(Pipe::new(i.try_into().unwrap(), j.try_into().unwrap(), 'S'));
                 }
             }
         }
-        panic!("No start in this field");
+        None
+    }
+
+    // Locations of every pipe in the loop through the start, in order around the loop.
+    // Empty if there's no start.
+    fn main_loop(&self) -> Vec<Point> {
+        let Some(mut prev) = self.find_start() else {
+            return Vec::new();
+        };
+        let mut current = prev.clone();
+        let mut pipes = vec![current.loc];
+
+        loop {
+            // Find next pipe in loop
+            for neighbours in &current.openings {
+                if let Some(maybe) = self.get_pipe(neighbours.x, neighbours.y) {
+                    if maybe != prev && current.connects_to(&maybe) {
+                        prev = current;
+                        current = maybe;
+                        break;
+                    }
+                }
+            }
+            if current.symbol == 'S' {
+                break;
+            }
+            pipes.push(current.loc);
+        }
+        pipes
     }
 
     fn get_pipe(&self, x: i32, y: i32) -> Option<Pipe> {
@@ -139,7 +160,14 @@ mod tests {
 
     #[test]
     fn check_day10_part2_case1() {
-        assert_eq!(day10("").1, "0".to_string())
+        let field = "...........\n.S-------7.\n.|F-----7|.\n.||.....||.\n.||.....||.\n.|L-7.F-J|.\n.|..|.|..|.\n.L--J.L--J.\n...........";
+        assert_eq!(part2(field.lines()), 4)
+    }
+
+    #[test]
+    fn check_day10_part2_case2() {
+        let field = "FF7FSF7F7F7F7F7F---7\nL|LJ||||||||||||F--J\nFL-7LJLJ||||||LJL-77\nF--JF--7||LJLJ7F7FJ-\nL---JF-JLJ.||-FJLJJ7\n|F|F-JF---7F7-L7L|7|\n|FFJF7L7F-JF7|JL---7\n7-L-JL7||F7|L7F-7F7|\nL.L7LFJ|||||FJL7||LJ\nL7JLJL-JLJLJL--JLJ.L";
+        assert_eq!(part2(field.lines()), 10)
     }
 
     #[test]
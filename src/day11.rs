@@ -64,7 +64,7 @@ impl SpacePicture {
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 struct Point {
     x: usize,
     y: usize,
@@ -0,0 +1,187 @@
+// Areas and lattice point counts for simple polygons with integer vertices.
+//
+// Shoelace formula: twice the signed area is Σ (x_i * y_{i+1} - x_{i+1} * y_i), positive
+// when the vertices run anticlockwise (with y pointing up).
+// Pick's theorem: A = I + B/2 - 1, where I counts the lattice points strictly inside
+// the polygon and B counts those on its boundary.
+
+use num::integer::gcd;
+use num::rational::Ratio;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Point {
+    pub x: i64,
+    pub y: i64,
+}
+
+impl Point {
+    pub fn new(x: i64, y: i64) -> Self {
+        Self { x, y }
+    }
+
+    #[allow(dead_code)]
+    pub fn step(&self, direction: Direction, length: i64) -> Self {
+        match direction {
+            Direction::Up => Self::new(self.x, self.y - length),
+            Direction::Down => Self::new(self.x, self.y + length),
+            Direction::Left => Self::new(self.x - length, self.y),
+            Direction::Right => Self::new(self.x + length, self.y),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[allow(dead_code)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+#[allow(dead_code)]
+impl Direction {
+    pub fn from_char(c: char) -> Self {
+        match c {
+            'U' | 'N' | '^' => Self::Up,
+            'D' | 'S' | 'v' => Self::Down,
+            'L' | 'W' | '<' => Self::Left,
+            'R' | 'E' | '>' => Self::Right,
+            _ => panic!("That's not a direction"),
+        }
+    }
+}
+
+pub struct Polygon {
+    vertices: Vec<Point>,
+}
+
+impl Polygon {
+    // Vertices in order around the polygon; the last joins back up to the first
+    pub fn new(vertices: Vec<Point>) -> Self {
+        Self { vertices }
+    }
+
+    // Trace out a polygon starting from the origin, e.g. from a dig plan
+    #[allow(dead_code)]
+    pub fn from_moves<I>(moves: I) -> Self
+    where
+        I: IntoIterator<Item = (Direction, i64)>,
+    {
+        let mut current = Point::new(0, 0);
+        let mut vertices = vec![current];
+        for (direction, length) in moves {
+            current = current.step(direction, length);
+            vertices.push(current);
+        }
+        if vertices.len() > 1 && vertices.last() == vertices.first() {
+            // Closed the loop; don't count the origin twice
+            vertices.pop();
+        }
+        Self { vertices }
+    }
+
+    #[allow(dead_code)]
+    pub fn vertices(&self) -> &[Point] {
+        &self.vertices
+    }
+
+    fn edges(&self) -> impl Iterator<Item = (&Point, &Point)> {
+        self.vertices
+            .iter()
+            .zip(self.vertices.iter().cycle().skip(1))
+    }
+
+    pub fn signed_double_area(&self) -> i128 {
+        self.edges()
+            .map(|(a, b)| a.x as i128 * b.y as i128 - b.x as i128 * a.y as i128)
+            .sum()
+    }
+
+    // Areas of lattice polygons are always whole or half numbers
+    #[allow(dead_code)]
+    pub fn signed_area(&self) -> Ratio<i128> {
+        Ratio::new(self.signed_double_area(), 2)
+    }
+
+    #[allow(dead_code)]
+    pub fn area(&self) -> Ratio<i128> {
+        Ratio::new(self.signed_double_area().abs(), 2)
+    }
+
+    // Lattice points lying on the edges, including the vertices
+    pub fn boundary_points(&self) -> i128 {
+        self.edges()
+            .map(|(a, b)| gcd(b.x as i128 - a.x as i128, b.y as i128 - a.y as i128))
+            .sum()
+    }
+
+    // Lattice points strictly inside the polygon, from Pick's theorem:
+    // I = A - B/2 + 1 = (2A - B + 2) / 2
+    pub fn interior_points(&self) -> i128 {
+        (self.signed_double_area().abs() - self.boundary_points() + 2) / 2
+    }
+
+    // Lattice points inside or on the boundary, e.g. the cubes dug out by a dig plan
+    #[allow(dead_code)]
+    pub fn enclosed_points(&self) -> i128 {
+        self.interior_points() + self.boundary_points()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_square() {
+        let square = Polygon::new(vec![
+            Point::new(0, 0),
+            Point::new(4, 0),
+            Point::new(4, 4),
+            Point::new(0, 4),
+        ]);
+        assert_eq!(square.signed_double_area(), 32);
+        assert_eq!(square.area(), Ratio::from_integer(16));
+        assert_eq!(square.boundary_points(), 16);
+        assert_eq!(square.interior_points(), 9);
+        assert_eq!(square.enclosed_points(), 25);
+    }
+
+    #[test]
+    fn check_orientation_and_half_areas() {
+        let triangle = Polygon::new(vec![Point::new(0, 0), Point::new(0, 3), Point::new(3, 0)]);
+        assert_eq!(triangle.signed_area(), Ratio::new(-9, 2));
+        assert_eq!(triangle.area(), Ratio::new(9, 2));
+        assert_eq!(triangle.boundary_points(), 9);
+        assert_eq!(triangle.interior_points(), 1);
+    }
+
+    #[test]
+    fn check_dig_plan() {
+        let plan = "R 6\nD 5\nL 2\nD 2\nR 2\nD 2\nL 5\nU 2\nL 1\nU 2\nR 2\nU 3\nL 2\nU 2";
+        let polygon = Polygon::from_moves(plan.lines().map(|line| {
+            let mut parts = line.split_ascii_whitespace();
+            (
+                Direction::from_char(parts.next().unwrap().chars().next().unwrap()),
+                parts.next().unwrap().parse().unwrap(),
+            )
+        }));
+        assert_eq!(polygon.vertices().len(), 14);
+        assert_eq!(polygon.boundary_points(), 38);
+        assert_eq!(polygon.enclosed_points(), 62);
+    }
+
+    #[test]
+    fn check_huge_coordinates() {
+        let side = 1_000_000_000_000;
+        let polygon = Polygon::from_moves([
+            (Direction::Right, side),
+            (Direction::Down, side),
+            (Direction::Left, side),
+            (Direction::Up, side),
+        ]);
+        let side = side as i128;
+        assert_eq!(polygon.enclosed_points(), (side + 1) * (side + 1));
+    }
+}
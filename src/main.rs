@@ -1,5 +1,6 @@
 #![recursion_limit = "512"]
 
+mod cycle;
 mod day00;
 mod day01;
 mod day02;
@@ -26,6 +27,10 @@ mod day22;
 mod day23;
 mod day24;
 mod day25;
+mod geometry;
+mod memo;
+mod number_theory;
+mod sequence;
 
 use std::env;
 
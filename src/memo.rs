@@ -0,0 +1,207 @@
+// Memoised recursion.
+//
+// The function being memoised is handed a `recurse` callback to use in place of
+// calling itself, so a plain closure (which can't name itself) works just as well
+// as a named fn:
+//
+//   let mut fib = Memoized::new(|recurse: &mut dyn FnMut(u64) -> u64, n: u64| {
+//       if n < 2 { n } else { recurse(n - 1) + recurse(n - 2) }
+//   });
+//   fib.get(90);
+//
+// Multiple arguments are passed as a tuple key.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+#[allow(dead_code)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+}
+
+#[allow(dead_code)]
+pub struct Memoized<K, V, F> {
+    function: F,
+    cache: HashMap<K, V>,
+    capacity: Option<usize>,
+    hits: u64,
+    misses: u64,
+}
+
+#[allow(dead_code)]
+impl<K, V, F> Memoized<K, V, F>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+    F: Fn(&mut dyn FnMut(K) -> V, K) -> V,
+{
+    pub fn new(function: F) -> Self {
+        Self {
+            function,
+            cache: HashMap::new(),
+            capacity: None,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    // Once the cache holds this many results, further results are computed but not
+    // stored.  Bounds memory use for huge state spaces at the cost of some repeat work.
+    pub fn with_capacity_bound(function: F, capacity: usize) -> Self {
+        Self {
+            capacity: Some(capacity),
+            ..Self::new(function)
+        }
+    }
+
+    pub fn get(&mut self, key: K) -> V {
+        let Self {
+            function,
+            cache,
+            capacity,
+            hits,
+            misses,
+        } = self;
+        let mut state = CacheState {
+            cache,
+            capacity: *capacity,
+            hits,
+            misses,
+        };
+        state.evaluate(function, key)
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits,
+            misses: self.misses,
+            entries: self.cache.len(),
+        }
+    }
+
+    // Forget all cached results (but keep counting hits and misses)
+    pub fn clear(&mut self) {
+        self.cache.clear();
+    }
+}
+
+// Everything but the function itself, so the function can be borrowed separately
+// while the cache is updated from inside the recursion
+#[allow(dead_code)]
+struct CacheState<'a, K, V> {
+    cache: &'a mut HashMap<K, V>,
+    capacity: Option<usize>,
+    hits: &'a mut u64,
+    misses: &'a mut u64,
+}
+
+#[allow(dead_code)]
+impl<K, V> CacheState<'_, K, V>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+{
+    fn evaluate<F>(&mut self, function: &F, key: K) -> V
+    where
+        F: Fn(&mut dyn FnMut(K) -> V, K) -> V,
+    {
+        if let Some(value) = self.cache.get(&key) {
+            *self.hits += 1;
+            return value.clone();
+        }
+        *self.misses += 1;
+
+        let value = function(&mut |k| self.evaluate(function, k), key.clone());
+        if self
+            .capacity
+            .is_none_or(|capacity| self.cache.len() < capacity)
+        {
+            self.cache.insert(key, value.clone());
+        }
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_fibonacci() {
+        let mut fib = Memoized::new(|recurse: &mut dyn FnMut(u64) -> u64, n: u64| {
+            if n < 2 {
+                n
+            } else {
+                recurse(n - 1) + recurse(n - 2)
+            }
+        });
+        assert_eq!(fib.get(90), 2880067194370816120);
+        assert_eq!(
+            fib.stats(),
+            CacheStats {
+                hits: 88,
+                misses: 91,
+                entries: 91
+            }
+        );
+        fib.get(90);
+        assert_eq!(fib.stats().hits, 89);
+    }
+
+    #[test]
+    fn check_capacity_bound() {
+        let mut fib = Memoized::with_capacity_bound(
+            |recurse: &mut dyn FnMut(u64) -> u64, n: u64| {
+                if n < 2 {
+                    n
+                } else {
+                    recurse(n - 1) + recurse(n - 2)
+                }
+            },
+            10,
+        );
+        assert_eq!(fib.get(20), 6765);
+        assert_eq!(fib.stats().entries, 10);
+    }
+
+    #[test]
+    fn check_spring_arrangements() {
+        // Count the ways to fill in the '?'s so that the runs of '#' match the groups,
+        // keyed on (position, group index, current run length)
+        fn arrangements(springs: &str, groups: &[usize]) -> u64 {
+            let springs = springs.as_bytes();
+            let mut count = Memoized::new(
+                |recurse: &mut dyn FnMut((usize, usize, usize)) -> u64,
+                 (position, group, run): (usize, usize, usize)| {
+                    if position == springs.len() {
+                        let finished = group == groups.len() && run == 0;
+                        let finishing = group == groups.len() - 1 && run == groups[group];
+                        return (finished || finishing).into();
+                    }
+                    let mut total = 0;
+                    if springs[position] != b'.' && group < groups.len() && run < groups[group] {
+                        total += recurse((position + 1, group, run + 1));
+                    }
+                    if springs[position] != b'#' {
+                        if run == 0 {
+                            total += recurse((position + 1, group, 0));
+                        } else if run == groups[group] {
+                            total += recurse((position + 1, group + 1, 0));
+                        }
+                    }
+                    total
+                },
+            );
+            count.get((0, 0, 0))
+        }
+
+        assert_eq!(arrangements("???.###", &[1, 1, 3]), 1);
+        assert_eq!(arrangements(".??..??...?##.", &[1, 1, 3]), 4);
+        assert_eq!(arrangements("?###????????", &[3, 2, 1]), 10);
+        let unfolded = ["?###????????"; 5].join("?");
+        assert_eq!(arrangements(&unfolded, &[3, 2, 1].repeat(5)), 506250);
+    }
+}
@@ -0,0 +1,146 @@
+// Modular arithmetic helpers, all in i128 so that products of two i64-sized
+// moduli never overflow.
+
+// Returns (g, x, y) such that a*x + b*y == g == gcd(a, b), with g >= 0
+pub fn extended_gcd(a: i128, b: i128) -> (i128, i128, i128) {
+    let (mut old_r, mut r) = (a, b);
+    let (mut old_x, mut x) = (1, 0);
+    let (mut old_y, mut y) = (0, 1);
+    while r != 0 {
+        let q = old_r / r;
+        (old_r, r) = (r, old_r - q * r);
+        (old_x, x) = (x, old_x - q * x);
+        (old_y, y) = (y, old_y - q * y);
+    }
+    if old_r < 0 {
+        (-old_r, -old_x, -old_y)
+    } else {
+        (old_r, old_x, old_y)
+    }
+}
+
+// The x in 0..m with a*x ≡ 1 (mod m), if a and m are coprime
+pub fn mod_inverse(a: i128, m: i128) -> Option<i128> {
+    let (g, x, _) = extended_gcd(a.rem_euclid(m), m);
+    if g == 1 {
+        Some(x.rem_euclid(m))
+    } else {
+        None
+    }
+}
+
+// Solve the system x ≡ residue (mod modulus) for every (residue, modulus) pair.
+// Moduli don't need to be coprime.  Returns the solution as (r, m) meaning
+// x ≡ r (mod m) with 0 <= r < m, or None if the congruences contradict each other.
+pub fn crt(congruences: &[(i128, i128)]) -> Option<(i128, i128)> {
+    congruences.iter().try_fold((0, 1), |(r1, m1), &(r2, m2)| {
+        let (g, _, _) = extended_gcd(m1, m2);
+        let diff = r2 - r1;
+        if diff % g != 0 {
+            return None;
+        }
+        // Solve r1 + m1*k ≡ r2 (mod m2) for k
+        let reduced_modulus = m2 / g;
+        let k = ((diff / g).rem_euclid(reduced_modulus) * mod_inverse(m1 / g, reduced_modulus)?)
+            .rem_euclid(reduced_modulus);
+        let lcm = m1 * reduced_modulus;
+        Some(((r1 + m1 * k).rem_euclid(lcm), lcm))
+    })
+}
+
+// The values start, start + step, start + 2*step, ...
+// A step of zero means the single value `start`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct Progression {
+    pub start: i128,
+    pub step: i128,
+}
+
+impl Progression {
+    pub fn new(start: i128, step: i128) -> Self {
+        assert!(step >= 0, "Progressions must not run backwards");
+        Self { start, step }
+    }
+
+    pub fn single(value: i128) -> Self {
+        Self::new(value, 0)
+    }
+
+    pub fn contains(&self, value: i128) -> bool {
+        if self.step == 0 {
+            value == self.start
+        } else {
+            value >= self.start && (value - self.start) % self.step == 0
+        }
+    }
+
+    // The values that appear in both progressions, which is itself a progression
+    pub fn intersect(&self, other: &Progression) -> Option<Progression> {
+        if self.step == 0 {
+            return other.contains(self.start).then_some(*self);
+        }
+        if other.step == 0 {
+            return self.contains(other.start).then_some(*other);
+        }
+        let (residue, modulus) = crt(&[(self.start, self.step), (other.start, other.step)])?;
+        // First solution at or after both starts
+        let lowest = i128::max(self.start, other.start);
+        let start = lowest + (residue - lowest).rem_euclid(modulus);
+        Some(Progression::new(start, modulus))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_extended_gcd() {
+        for (a, b) in [(240, 46), (46, 240), (-12, 18), (7, 0), (0, 7), (17, 5)] {
+            let (g, x, y) = extended_gcd(a, b);
+            assert_eq!(a * x + b * y, g);
+            assert_eq!(g, num::integer::gcd(a, b));
+        }
+    }
+
+    #[test]
+    fn check_mod_inverse() {
+        assert_eq!(mod_inverse(3, 11), Some(4));
+        assert_eq!(mod_inverse(-3, 11), Some(7));
+        assert_eq!(mod_inverse(6, 9), None);
+    }
+
+    #[test]
+    fn check_crt_coprime() {
+        assert_eq!(crt(&[(2, 3), (3, 5), (2, 7)]), Some((23, 105)));
+    }
+
+    #[test]
+    fn check_crt_non_coprime() {
+        assert_eq!(crt(&[(2, 6), (8, 10)]), Some((8, 30)));
+        assert_eq!(crt(&[(1, 6), (2, 10)]), None);
+        assert_eq!(crt(&[]), Some((0, 1)));
+    }
+
+    #[test]
+    fn check_progression_intersection() {
+        let a = Progression::new(3, 4); // 3, 7, 11, 15, 19, ...
+        let b = Progression::new(5, 6); // 5, 11, 17, 23, ...
+        assert_eq!(a.intersect(&b), Some(Progression::new(11, 12)));
+
+        // Residues line up, but only after the later start
+        let c = Progression::new(27, 4);
+        assert_eq!(c.intersect(&b), Some(Progression::new(35, 12)));
+
+        let evens = Progression::new(0, 2);
+        let odds = Progression::new(1, 2);
+        assert_eq!(evens.intersect(&odds), None);
+
+        assert_eq!(
+            Progression::single(15).intersect(&a),
+            Some(Progression::single(15))
+        );
+        assert_eq!(Progression::single(1).intersect(&a), None);
+        assert_eq!(a.intersect(&Progression::single(2)), None);
+    }
+}
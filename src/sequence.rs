@@ -0,0 +1,255 @@
+// Finite differences of integer sequences.
+//
+// If some row of the difference table is all zeros, the sequence is given by a
+// polynomial whose degree is the number of rows before that one, less one.  Newton's
+// forward difference formula then gives that polynomial exactly:
+//   f(x) = Σ Δ^j f(0) * C(x, j)
+// which can be evaluated at any integer x, before or after the known values.
+
+use num::{BigInt, BigRational, One, Signed, Zero};
+use std::fmt;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SequenceError {
+    Empty,
+    NoPolynomialFit { length: usize },
+}
+
+impl fmt::Display for SequenceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "Can't fit a polynomial to an empty sequence"),
+            Self::NoPolynomialFit { length } => write!(
+                f,
+                "Differences of these {} values never reach zero, so no polynomial fits",
+                length
+            ),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct DifferenceTable {
+    // rows[0] is the sequence itself, each following row the differences of the one
+    // before.  The final (all zero) row isn't stored.
+    rows: Vec<Vec<BigInt>>,
+    length: usize,
+}
+
+impl DifferenceTable {
+    pub fn new<I, T>(sequence: I) -> Result<Self, SequenceError>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<BigInt>,
+    {
+        let mut next_row: Vec<BigInt> = sequence.into_iter().map(Into::into).collect();
+        if next_row.is_empty() {
+            return Err(SequenceError::Empty);
+        }
+        let length = next_row.len();
+        if length == 1 {
+            // A lone value can't be checked against anything, so take it as a constant
+            return Ok(Self {
+                rows: vec![next_row],
+                length,
+            });
+        }
+
+        let mut rows = Vec::new();
+        while !next_row.iter().all(Zero::is_zero) {
+            if next_row.len() == 1 {
+                // Nothing left to confirm that the differences have settled
+                return Err(SequenceError::NoPolynomialFit { length });
+            }
+            let differences = next_row.windows(2).map(|s| &s[1] - &s[0]).collect();
+            rows.push(next_row);
+            next_row = differences;
+        }
+        Ok(Self { rows, length })
+    }
+
+    #[allow(dead_code)]
+    pub fn rows(&self) -> &[Vec<BigInt>] {
+        &self.rows
+    }
+
+    // The zero sequence is treated as degree 0
+    #[allow(dead_code)]
+    pub fn degree(&self) -> usize {
+        self.rows.len().saturating_sub(1)
+    }
+
+    // Value of the polynomial at any position, where 0 is the first known value
+    pub fn value_at(&self, x: i64) -> BigInt {
+        let x = BigInt::from(x);
+        let mut binomial = BigInt::one(); // C(x, j)
+        let mut total = BigInt::zero();
+        for (j, row) in self.rows.iter().enumerate() {
+            total += &row[0] * &binomial;
+            binomial = binomial * (&x - j) / (j + 1);
+        }
+        total
+    }
+
+    // The value k steps after the last known value
+    pub fn forward(&self, k: i64) -> BigInt {
+        let last: i64 = (self.length - 1).try_into().unwrap();
+        self.value_at(last + k)
+    }
+
+    // The value k steps before the first known value
+    pub fn backward(&self, k: i64) -> BigInt {
+        self.value_at(-k)
+    }
+
+    #[allow(dead_code)]
+    pub fn polynomial(&self) -> Polynomial {
+        // Expand each C(x, j) = x(x-1)...(x-j+1) / j! into powers of x
+        let mut falling_factorial = vec![BigInt::one()];
+        let mut factorial = BigInt::one();
+        let mut coefficients = vec![BigRational::zero(); self.rows.len()];
+
+        for (j, row) in self.rows.iter().enumerate() {
+            for (power, coefficient) in falling_factorial.iter().enumerate() {
+                coefficients[power] += BigRational::new(&row[0] * coefficient, factorial.clone());
+            }
+            // Multiply through by (x - j)
+            let mut next = vec![BigInt::zero(); falling_factorial.len() + 1];
+            for (power, coefficient) in falling_factorial.iter().enumerate() {
+                next[power + 1] += coefficient;
+                next[power] -= coefficient * j;
+            }
+            falling_factorial = next;
+            factorial *= j + 1;
+        }
+
+        Polynomial { coefficients }
+    }
+}
+
+// Coefficients in increasing powers of x
+#[derive(Debug, PartialEq, Eq)]
+#[allow(dead_code)]
+pub struct Polynomial {
+    pub coefficients: Vec<BigRational>,
+}
+
+#[allow(dead_code)]
+impl Polynomial {
+    pub fn evaluate(&self, x: &BigRational) -> BigRational {
+        // Horner's method
+        self.coefficients
+            .iter()
+            .rev()
+            .fold(BigRational::zero(), |acc, c| acc * x + c)
+    }
+}
+
+impl fmt::Display for Polynomial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut terms = self
+            .coefficients
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, c)| !c.is_zero())
+            .peekable();
+        if terms.peek().is_none() {
+            return write!(f, "0");
+        }
+
+        let mut first = true;
+        for (power, coefficient) in terms {
+            let sign = if coefficient.is_negative() { "-" } else { "+" };
+            if first {
+                if coefficient.is_negative() {
+                    write!(f, "-")?;
+                }
+            } else {
+                write!(f, " {} ", sign)?;
+            }
+            first = false;
+
+            let magnitude = coefficient.abs();
+            if power == 0 || !magnitude.is_one() {
+                write!(f, "{}", magnitude)?;
+            }
+            match power {
+                0 => {}
+                1 => write!(f, "x")?,
+                _ => write!(f, "x^{}", power)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_degree_and_extrapolation() {
+        let table = DifferenceTable::new([10, 13, 16, 21, 30, 45]).unwrap();
+        assert_eq!(table.degree(), 3);
+        assert_eq!(table.forward(1), BigInt::from(68));
+        assert_eq!(table.backward(1), BigInt::from(5));
+        assert_eq!(table.forward(3), table.value_at(8));
+    }
+
+    #[test]
+    fn check_no_overflow() {
+        let table = DifferenceTable::new([i64::MAX - 2, i64::MAX - 1, i64::MAX]).unwrap();
+        assert_eq!(table.degree(), 1);
+        assert_eq!(table.forward(1), BigInt::from(i64::MAX) + 1);
+    }
+
+    #[test]
+    fn check_constant_and_zero_sequences() {
+        let table = DifferenceTable::new([7, 7]).unwrap();
+        assert_eq!(table.degree(), 0);
+        assert_eq!(table.backward(100), BigInt::from(7));
+
+        let table = DifferenceTable::new([5]).unwrap();
+        assert_eq!(table.degree(), 0);
+        assert_eq!(table.forward(3), BigInt::from(5));
+        assert_eq!(table.polynomial().to_string(), "5");
+
+        let table = DifferenceTable::new([0, 0, 0]).unwrap();
+        assert_eq!(table.forward(5), BigInt::zero());
+        assert_eq!(table.polynomial().to_string(), "0");
+    }
+
+    #[test]
+    fn check_no_fit() {
+        assert_eq!(
+            DifferenceTable::new([1, 2, 4, 8, 16]).unwrap_err(),
+            SequenceError::NoPolynomialFit { length: 5 }
+        );
+        assert_eq!(
+            DifferenceTable::new([1, 3]).unwrap_err(),
+            SequenceError::NoPolynomialFit { length: 2 }
+        );
+        assert_eq!(
+            DifferenceTable::new(Vec::<i64>::new()).unwrap_err(),
+            SequenceError::Empty
+        );
+    }
+
+    #[test]
+    fn check_polynomial() {
+        // Triangle numbers, starting from x = 0: x(x+1)/2 + 1
+        let table = DifferenceTable::new([1, 2, 4, 7, 11]).unwrap();
+        let polynomial = table.polynomial();
+        assert_eq!(polynomial.to_string(), "1/2x^2 + 1/2x + 1");
+        for x in -5..10 {
+            assert_eq!(
+                polynomial.evaluate(&BigRational::from_integer(x.into())),
+                BigRational::from_integer(table.value_at(x))
+            );
+        }
+
+        let table = DifferenceTable::new([3, 1, -1]).unwrap();
+        assert_eq!(table.polynomial().to_string(), "-2x + 3");
+    }
+}